@@ -1,10 +1,23 @@
 //! this module contains structs and logic for making graphs as displayed in the background of
 //! xtchd.com 
 
-use std::{fmt, collections::HashMap};
-use serde::{Serialize, Deserialize};
+use std::{fmt, collections::{HashMap, HashSet, hash_map::Entry}};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use serde_json;
 
+mod algorithms;
+pub use algorithms::ALL_PATHS_MAX_LEN;
+mod cache;
+mod concurrent;
+pub use concurrent::{ConcurrentGraphBuilder, GraphSender};
+mod export;
+pub use export::{CompactGraph, GraphSchema};
+mod macros;
+#[cfg(feature = "svg")]
+mod svg;
+#[cfg(test)]
+mod test_support;
+
 
 /// 3d-force-directed is a javascript library. As such, it can accept a wide range of objects as a node: the key properties are that each
 /// node has a .id and .name property.  
@@ -26,14 +39,73 @@ pub struct Node<NV, PK, T> {
     pub name: String,
     /// The props field captures any props specific to the selected variant 
     pub props: T,
+    /// A longer description, e.g. for an HTML hover card, while name stays the short label 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub desc: Option<String>,
+    /// Epoch millis at which the source data for this node last changed, see Graph::merge_newest 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+    /// Small status icons (icon names or URLs) the frontend overlays on the node, e.g. "verified" or "flagged" 
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub badges: Vec<String>,
+    /// Some(true) if the node has neighbors that are not loaded yet, so the frontend offers to expand it 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_more: Option<bool>,
+    /// A geometry hint such as "sphere", "cube" or "sprite" that the frontend's nodeThreeObject maps to a 3D object 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shape: Option<String>,
+}
+
+impl<NV, PK, T> Node<NV, PK, T>  {
+    /// A node with the given identity and props, and the optional fields (desc, updated_at, badges,
+    /// has_more, shape) unset. Set those afterwards or with struct update syntax as needed 
+    pub fn new(variant: NV, variant_pk: PK, id: String, name: String, props: T) -> Self {
+        Node{variant, variant_pk, id, name, props, desc: None, updated_at: None, badges: Vec::new(), has_more: None, shape: None}
+    }
 }
 
 impl<NV: Serialize, PK: Serialize, T: Serialize> Node<NV, PK, T>  {
     pub fn to_node_json(&self) -> Result<serde_json::Value, serde_json::Error> {
-        serde_json::to_value(&self)
+        serde_json::to_value(self)
     }
 }
 
+impl<NV: InfallibleProps, PK: InfallibleProps, T: InfallibleProps> Node<NV, PK, T>  {
+    /// Like to_node_json, for nodes that can never fail to serialize, so there is no Result to unwrap.
+    /// This needs the variant and primary key to be InfallibleProps as well as the props, since a map-keyed
+    /// pk fails just like map-keyed props would. For an enum variant, implement the marker on the enum 
+    pub fn to_value_infallible(&self) -> serde_json::Value {
+        self.to_node_json().expect("node with infallible variant, pk and props failed to serialize")
+    }
+}
+
+
+/// Marker for types whose conversion to a serde_json::Value cannot fail, enabling Node::to_value_infallible
+/// when a node's variant, pk and props all implement it.
+/// serde_json::to_value only fails for maps with non-string keys or a Serialize impl that returns an error,
+/// so this holds for (), strings, numbers (non-finite floats become null), bools, serde_json::Value, Props,
+/// and references, Options, Vecs and tuples of these. Implement it for your own types (such as a variant enum
+/// deriving Serialize) if they are built from such types 
+pub trait InfallibleProps: Serialize {}
+
+impl InfallibleProps for () {}
+impl InfallibleProps for bool {}
+impl InfallibleProps for str {}
+impl InfallibleProps for String {}
+impl InfallibleProps for i32 {}
+impl InfallibleProps for i64 {}
+impl InfallibleProps for u32 {}
+impl InfallibleProps for u64 {}
+impl InfallibleProps for usize {}
+impl InfallibleProps for f64 {}
+impl InfallibleProps for serde_json::Value {}
+impl InfallibleProps for Props {}
+impl<T: InfallibleProps + ?Sized> InfallibleProps for &T {}
+impl<T: InfallibleProps> InfallibleProps for Option<T> {}
+impl<A: InfallibleProps, B: InfallibleProps> InfallibleProps for (A, B) {}
+impl<A: InfallibleProps, B: InfallibleProps, C: InfallibleProps> InfallibleProps for (A, B, C) {}
+impl<T: InfallibleProps> InfallibleProps for Vec<T> {}
+
 
 
 /// Implementing this trait on a struct will makes it ergonomic to convert it to a node 
@@ -48,13 +120,38 @@ pub trait ToNode<NV: fmt::Display, PK: fmt::Debug, T> {
         None
     }
     fn node_props(&self) -> T;
+    /// An optional longer description shown in the frontend's hover card 
+    fn node_desc(&self) -> Option<String> {
+        None
+    }
+    /// When the node's source data last changed, in epoch millis, if known 
+    fn node_updated_at(&self) -> Option<i64> {
+        None
+    }
+    /// Status icons to overlay on the node, none by default 
+    fn node_badges(&self) -> Vec<String> {
+        Vec::new()
+    }
+    /// Whether the node has neighbors left out of this graph, for lazy loading. Unknown (None) by default 
+    fn node_has_more(&self) -> Option<bool> {
+        None
+    }
+    /// Which geometry the frontend should render the node as, e.g. to give each variant its own shape. None by default 
+    fn node_shape(&self) -> Option<String> {
+        None
+    }
     fn to_node(&self) -> Node<NV, PK, T> {
         let variant = self.node_variant();
         let variant_pk = self.node_pk();
         let id = self.node_id();
         let name = self.node_name();
         let props = self.node_props();
-        Node{variant, variant_pk, id, name, props}
+        let desc = self.node_desc();
+        let updated_at = self.node_updated_at();
+        let badges = self.node_badges();
+        let has_more = self.node_has_more();
+        let shape = self.node_shape();
+        Node{variant, variant_pk, id, name, props, desc, updated_at, badges, has_more, shape}
     }
     /// Edes can have labels too in 3d-force=directed. This optional method captures the "nodes' contribution" to the endge label
     /// if it is an edge source 
@@ -88,6 +185,36 @@ pub trait ToNodeJSON<NV, PK, T>: ToNode<NV, PK, T> where
 }
 
 
+/// ToNode::node_props returns an owned T, which forces a clone when the props already live in self.
+/// For large props, implement ToNodeRef as well so the node can be built and serialized by reference.
+/// (ToNode::node_props is still required, typically as a clone, for the paths that need an owned Node) 
+pub trait ToNodeRef<NV, PK, T>: ToNode<NV, PK, T> where 
+    NV: Serialize + fmt::Display,
+    PK: Serialize + fmt::Debug, 
+    T:  Serialize  
+{
+    fn node_props_ref(&self) -> &T;
+    /// Like to_node, but the props are borrowed from self rather than cloned 
+    fn to_node_borrowed(&self) -> Node<NV, PK, &T> {
+        let variant = self.node_variant();
+        let variant_pk = self.node_pk();
+        let id = self.node_id();
+        let name = self.node_name();
+        let props = self.node_props_ref();
+        let desc = self.node_desc();
+        let updated_at = self.node_updated_at();
+        let badges = self.node_badges();
+        let has_more = self.node_has_more();
+        let shape = self.node_shape();
+        Node{variant, variant_pk, id, name, props, desc, updated_at, badges, has_more, shape}
+    }
+    /// Serialize the node without cloning its props. The JSON is identical to that of the owned path 
+    fn to_node_json_borrowed(&self) -> Result<serde_json::Value, serde_json::Error> {
+        self.to_node_borrowed().to_node_json()
+    }
+}
+
+
 
 
 
@@ -107,12 +234,28 @@ pub struct Edge<EV, PK, T> {
     pub target: String,
     /// An arbitrary struct to capture properties for this node 
     pub props: T,
+    /// Epoch millis from which the edge is valid (inclusive); None means valid since forever 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_from: Option<i64>,
+    /// Epoch millis until which the edge is valid (exclusive); None means valid forever 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_to: Option<i64>,
+    /// The human readable label shown for the edge (e.g. "wrote"), as opposed to the machine readable variant 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Group key for the frontend's hierarchical edge bundling: edges sharing a bundle are drawn together 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<String>,
+    /// Whether the frontend draws an arrow for this edge, for graphs mixing directed and undirected relations.
+    /// None leaves it to the frontend's default 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub directed: Option<bool>,
 }
 
 
 impl<EV: Serialize, PK: Serialize, T: Serialize> Edge<EV, PK, T>  {
     pub fn to_edge_json(&self) -> Result<serde_json::Value, serde_json::Error> {
-        serde_json::to_value(&self)
+        serde_json::to_value(self)
     }
 }
 
@@ -122,12 +265,34 @@ impl<EV: Serialize, PK: Serialize, T: Serialize> Edge<EV, PK, T>  {
 pub trait ToEdge<EV: fmt::Display, PK: fmt::Debug, T> {
     fn edge_variant(&self) -> EV;
     fn edge_pk(&self) -> PK;
+    /// The pk as it appears in edge_id. The default is its Debug formatting, which keeps existing ids unchanged;
+    /// override it so ids stay stable even if the pk type's Debug output changes 
+    fn edge_pk_string(&self) -> String {
+        format!("{:?}", &self.edge_pk())
+    }
     fn edge_id(&self) -> String {
-        format!("{}|{:?}", &self.edge_variant(), &self.edge_pk())
+        format!("{}|{}", &self.edge_variant(), &self.edge_pk_string())
     }
     fn edge_source(&self) -> String;
     fn edge_target(&self) -> String;
     fn edge_props(&self) -> T;
+    /// For temporal graphs: the (valid_from, valid_to) epoch millis window in which the edge exists.
+    /// Either end may be None for an open interval; by default edges are always valid 
+    fn edge_validity(&self) -> (Option<i64>, Option<i64>) {
+        (None, None)
+    }
+    /// The label displayed for the edge. By default it is the Display string of the variant 
+    fn edge_label(&self) -> Option<String> {
+        Some(self.edge_variant().to_string())
+    }
+    /// The edge bundling group of the edge, if any. None by default 
+    fn edge_bundle(&self) -> Option<String> {
+        None
+    }
+    /// Whether the edge is directed (drawn with an arrow), if it matters. None by default 
+    fn edge_directed(&self) -> Option<bool> {
+        None
+    }
     fn to_edge(&self) -> Edge<EV, PK, T> {
         let variant = self.edge_variant();
         let variant_pk = self.edge_pk();
@@ -135,7 +300,11 @@ pub trait ToEdge<EV: fmt::Display, PK: fmt::Debug, T> {
         let source = self.edge_source();
         let target = self.edge_target();
         let props = self.edge_props();
-        Edge{variant, variant_pk, id, source, target, props}
+        let (valid_from, valid_to) = self.edge_validity();
+        let label = self.edge_label();
+        let bundle = self.edge_bundle();
+        let directed = self.edge_directed();
+        Edge{variant, variant_pk, id, source, target, props, valid_from, valid_to, label, bundle, directed}
     }
 }
 
@@ -158,6 +327,232 @@ pub trait ToEdgeJSON<EV, PK, T>: ToEdge<EV, PK, T> where
 }
 
 
+/// Some relationships connect more than two nodes, e.g. a coauthorship of three people.
+/// A HyperEdge records all of its member node ids; the frontend can draw it as a clique or a group node 
+#[derive(Serialize, Deserialize)]
+pub struct HyperEdge<EV, PK, T> {
+    /// The type of hyperedge, typically a String or an enum that implements std::fmt::Display 
+    pub variant: EV,
+    /// The primary key within the variant type, typically i32, String, or a tuple
+    pub variant_pk: PK,
+    /// This id will be unique to the hyperedge 
+    pub id: String,
+    /// The ids of the member nodes 
+    pub members: Vec<String>,
+    /// An arbitrary struct to capture properties for this hyperedge 
+    pub props: T,
+}
+
+
+impl<EV: Serialize, PK: Serialize, T: Serialize> HyperEdge<EV, PK, T>  {
+    pub fn to_hyperedge_json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
+
+
+/// Implementing this trait on a struct will makes it ergonomic to convert it to a hyperedge 
+pub trait ToHyperEdge<EV: fmt::Display, PK: fmt::Debug, T> {
+    fn hyperedge_variant(&self) -> EV;
+    fn hyperedge_pk(&self) -> PK;
+    fn hyperedge_id(&self) -> String {
+        format!("{}|{:?}", self.hyperedge_variant(), self.hyperedge_pk())
+    }
+    fn hyperedge_members(&self) -> Vec<String>;
+    fn hyperedge_props(&self) -> T;
+    fn to_hyperedge(&self) -> HyperEdge<EV, PK, T> {
+        let variant = self.hyperedge_variant();
+        let variant_pk = self.hyperedge_pk();
+        let id = self.hyperedge_id();
+        let members = self.hyperedge_members();
+        let props = self.hyperedge_props();
+        HyperEdge{variant, variant_pk, id, members, props}
+    }
+}
+
+
+/// Props are often just "a couple of key/values". Rather than building a serde_json::Value by hand,
+/// Props offers a chainable builder: 
+/// `Props::new().set("weight", 3).set("comment", "first author")`
+/// Since it serializes as a plain JSON object, it can be passed directly as the props of a Node
+/// or as the edge_props argument of Graph::source_edge_target 
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Props(serde_json::Map<String, serde_json::Value>);
+
+/// Props used for an edge, i.e. the edge_props argument to Graph::source_edge_target
+pub type EdgeProps = Props;
+/// Props used for a node, i.e. the T in Node<NV, PK, T> 
+pub type NodeProps = Props;
+
+impl Props {
+    /// return a new builder with no keys 
+    pub fn new() -> Self {
+        Props(serde_json::Map::new())
+    }
+
+    /// set key to value, overwriting any previous value for that key 
+    pub fn set<V: Into<serde_json::Value>>(mut self, key: &str, value: V) -> Self {
+        self.0.insert(key.to_string(), value.into());
+        self
+    }
+
+    /// consume the builder, returning the JSON object 
+    pub fn to_value(self) -> serde_json::Value {
+        serde_json::Value::Object(self.0)
+    }
+}
+
+impl From<Props> for serde_json::Value {
+    fn from(props: Props) -> Self {
+        props.to_value()
+    }
+}
+
+
+/// The errors that can arise when manipulating a Graph 
+#[derive(Debug)]
+pub enum GraphError {
+    /// No node with this id exists in the graph 
+    NodeNotFound(String),
+    /// These edge ids have a source or target node that is not in the graph 
+    DanglingEdges(Vec<String>),
+    /// The JSON could not be (de)serialized 
+    Json(serde_json::Error),
+    /// A CompactGraph referenced an index beyond the end of its id table 
+    UnknownIndex(u64),
+    /// A CompactGraph held this value where a node index was expected, rather than a non-negative integer 
+    InvalidCompactIndex(serde_json::Value),
+    /// A CompactGraph held this edge or hyperedge without the string id it is keyed by 
+    MissingCompactId(serde_json::Value),
+    /// The node, edge or hyperedge with this id is stored as a JSON value that is not an object, so it cannot carry its id 
+    NotAnObject(String),
+    /// The graph has a directed cycle through these nodes, in order 
+    Cycle { nodes: Vec<String> },
+    /// No edge with this id exists in the graph 
+    EdgeNotFound(String),
+    /// These node ids have an empty or missing name, which the frontend cannot display 
+    UnnamedNodes(Vec<String>),
+    /// These hyperedge ids have a member node that is not in the graph 
+    DanglingHyperedges(Vec<String>),
+    /// A GraphSender was used after its ConcurrentGraphBuilder was dropped without finishing 
+    BuilderDropped,
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::NodeNotFound(id) => write!(f, "no node with id {} in the graph", id),
+            GraphError::DanglingEdges(ids) => write!(f, "edges with a missing source or target: {}", ids.join(", ")),
+            GraphError::Json(e) => write!(f, "{}", e),
+            GraphError::UnknownIndex(i) => write!(f, "index {} is not in the id table", i),
+            GraphError::InvalidCompactIndex(value) => write!(f, "{} is not a valid node index", value),
+            GraphError::MissingCompactId(value) => write!(f, "{} has no id", value),
+            GraphError::NotAnObject(id) => write!(f, "{} is not a JSON object", id),
+            GraphError::Cycle{nodes} => write!(f, "the graph has a cycle: {}", nodes.join(" -> ")),
+            GraphError::EdgeNotFound(id) => write!(f, "no edge with id {} in the graph", id),
+            GraphError::UnnamedNodes(ids) => write!(f, "nodes without a name: {}", ids.join(", ")),
+            GraphError::DanglingHyperedges(ids) => write!(f, "hyperedges with a missing member: {}", ids.join(", ")),
+            GraphError::BuilderDropped => write!(f, "the graph builder was dropped before finishing"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for GraphError {
+    fn from(e: serde_json::Error) -> Self {
+        GraphError::Json(e)
+    }
+}
+
+
+/// the (source, target) node ids recorded in an edge's JSON 
+fn edge_ends(edge: &serde_json::Value) -> (&str, &str) {
+    (edge["source"].as_str().unwrap_or_default(), edge["target"].as_str().unwrap_or_default())
+}
+
+
+/// the variant prefix of a node id built by the default ToNode::node_id, i.e. "variant|pk" 
+fn variant_of_id(id: &str) -> &str {
+    id.split('|').next().unwrap_or_default()
+}
+
+
+/// move collections[from] under to, merging into any existing collection there (the moved values win on an id
+/// collision), and rewrite the variant field of each moved value 
+fn rename_variant(collections: &mut HashMap<String, HashMap<String, serde_json::Value>>, from: &str, to: &str) {
+    if from == to {
+        return;
+    }
+    let Some(moved) = collections.remove(from) else {
+        return;
+    };
+    let target = collections.entry(to.to_string()).or_default();
+    for (id, mut value) in moved {
+        if let Some(object) = value.as_object_mut() {
+            object.insert("variant".to_string(), to.into());
+        }
+        target.insert(id, value);
+    }
+}
+
+
+/// replace every number in value that is not a finite f64 with null, returning how many were replaced.
+/// with arbitrary_precision as_f64 is None for out-of-range literals, so anything that is neither an integer
+/// nor a finite f64 counts 
+fn null_non_finite(value: &mut serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Number(n) if !n.is_i64() && !n.is_u64() && n.as_f64().is_none_or(|f| !f.is_finite()) => {
+            *value = serde_json::Value::Null;
+            1
+        },
+        serde_json::Value::Array(items) => items.iter_mut().map(null_non_finite).sum(),
+        serde_json::Value::Object(map) => map.values_mut().map(null_non_finite).sum(),
+        _ => 0,
+    }
+}
+
+
+/// merge patch into target: keys of a patch object are merged recursively into a target object,
+/// anything else in patch replaces what is in target 
+fn deep_merge(target: &mut serde_json::Value, patch: serde_json::Value) {
+    match (target, patch) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    },
+                }
+            }
+        },
+        (target, patch) => *target = patch,
+    }
+}
+
+
+/// the current unix time in seconds, or 0 if the clock is set before 1970 
+#[cfg(feature = "time")]
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+
+/// The most pairs Graph::missing_edges returns, since a variant of n nodes can have n(n-1)/2 of them 
+pub const MISSING_EDGES_LIMIT: usize = 100_000;
+
+
 /// A graph contains both nodes and edges, collected first by type and next by id 
 /// However, nodes and edges are reduced to simply serde_json::Value objects!  
 /// This is because this struct is intended for serialization for http transmission
@@ -166,7 +561,56 @@ pub trait ToEdgeJSON<EV, PK, T>: ToEdge<EV, PK, T> where
 pub struct Graph {
     pub nodes: HashMap<String, HashMap<String, serde_json::Value>>,
     pub edges: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// Hyperedges connecting any number of nodes, collected by type and then id like edges.
+    /// Methods that remove or merge nodes keep their members in step: removed nodes are dropped from the members
+    /// (and a hyperedge left without members is removed), merged nodes are replaced by the node they merged into 
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub hyperedges: HashMap<String, HashMap<String, serde_json::Value>>,
+    /// Graph-level settings for the frontend 
+    #[serde(default)]
+    pub meta: GraphMeta,
+    /// Seed for the frontend's layout RNG, so the same graph lays out the same way on every load.
+    /// None lets the frontend pick its own 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout_seed: Option<u64>,
+    /// Unix time in seconds at which Graph::new created this graph, so clients can apply a TTL.
+    /// Graphs deserialized from JSON without it get 0 
+    #[cfg(feature = "time")]
+    #[serde(default)]
+    pub generated_at: i64,
+    /// Optional secondary index of node id -> variant, see Graph::with_index 
+    #[serde(skip)]
+    index: Option<HashMap<String, String>>,
+    /// Optional cache of encoded nodes and edges, see Graph::with_cache 
+    #[serde(skip)]
+    cache: Option<cache::FragmentCache>,
+    /// Optional hook run on each node's JSON as it is added, see Graph::with_node_decorator 
+    #[serde(skip)]
+    decorator: Option<NodeDecorator>,
+}
+
+
+/// A hook run on the JSON of every node added to a Graph, see Graph::with_node_decorator 
+type NodeDecorator = Box<dyn Fn(&mut serde_json::Value) + Send + Sync>;
 
+
+/// Graph-level settings for the frontend, as opposed to the nodes and edges themselves 
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GraphMeta {
+    /// The id of the node to zoom to when the graph is displayed, as passed to graph3d.js :: zoomToId() 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zoom_id: Option<String>,
+    /// node variant -> style (color, size etc.) the frontend applies to every node of that variant,
+    /// which keeps per-node payloads small while theming consistently 
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub variant_styles: HashMap<String, serde_json::Value>,
+}
+
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 
@@ -176,7 +620,27 @@ impl Graph
     pub fn new() -> Self {
         let nodes = HashMap::new();
         let edges = HashMap::new();
-        Graph{nodes, edges}
+        let hyperedges = HashMap::new();
+        let meta = GraphMeta::default();
+        Graph{
+            nodes,
+            edges,
+            hyperedges,
+            meta,
+            layout_seed: None,
+            #[cfg(feature = "time")]
+            generated_at: unix_now(),
+            index: None,
+            cache: None,
+            decorator: None,
+        }
+    }
+
+
+    /// How many seconds old the graph is at unix time now (in seconds), according to generated_at 
+    #[cfg(feature = "time")]
+    pub fn age_seconds(&self, now: i64) -> i64 {
+        now - self.generated_at
     }
 
 
@@ -185,18 +649,236 @@ impl Graph
         PK: Serialize + fmt::Debug, 
         T:  Serialize  
     {
-        let json = node.to_node_json()?;
+        let mut json = node.to_node_json()?;
+        if let Some(decorate) = &self.decorator {
+            decorate(&mut json);
+        }
         let collection = node.variant.to_string();
         let id = node.id.clone();
+        if let Some(index) = self.index.as_mut() {
+            index.insert(id.clone(), collection.clone());
+        }
+        self.touch_node(&collection, &id);
         let _x = self.nodes
             .entry(collection)
-            .or_insert(HashMap::new())
+            .or_default()
             .insert(id, json);
         Ok(())
     }
 
 
-    pub fn add_node_from<NV, PK, T>(&mut self, n : &(dyn ToNodeJSON<NV, PK, T>)) -> Result<Node<NV, PK, T>, serde_json::Error> where 
+    /// Like add_node, but if a node with the same id already exists under the variant, the new node's JSON is merged into
+    /// it instead of replacing it: objects (such as props) are merged recursively and other values are overwritten.
+    /// Fields no longer set on the new node, like x/y/z written by a layout pass or a desc that is now None, are kept 
+    pub fn add_node_merge<NV, PK, T>(&mut self, node: &Node<NV, PK, T>) -> Result<(), serde_json::Error> where 
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        let mut json = node.to_node_json()?;
+        if let Some(decorate) = &self.decorator {
+            decorate(&mut json);
+        }
+        let collection = node.variant.to_string();
+        if let Some(index) = self.index.as_mut() {
+            index.insert(node.id.clone(), collection.clone());
+        }
+        self.touch_node(&collection, &node.id);
+        match self.nodes.entry(collection).or_default().entry(node.id.clone()) {
+            Entry::Occupied(mut existing) => deep_merge(existing.get_mut(), json),
+            Entry::Vacant(vacant) => {
+                vacant.insert(json);
+            },
+        }
+        Ok(())
+    }
+
+
+    /// Return a new empty graph that maintains a secondary index of node id -> variant,
+    /// making lookups by bare id (such as get_node_by_id) O(1) instead of a scan over every variant.
+    /// The cost is one extra copy of every node id and variant name in memory.
+    /// Graph's own methods keep the index consistent, but code that edits the pub nodes map directly must call
+    /// build_index afterwards. If an id is present under several variants, the index records only one of them 
+    pub fn with_index() -> Self {
+        let mut graph = Graph::new();
+        graph.index = Some(HashMap::new());
+        graph
+    }
+
+
+    /// (Re)build the id -> variant index from the current nodes, enabling it if it was not already 
+    pub fn build_index(&mut self) {
+        let index = self.nodes.iter()
+            .flat_map(|(variant, collection)| collection.keys().map(move |id| (id.clone(), variant.clone())))
+            .collect();
+        self.index = Some(index);
+    }
+
+
+    /// rebuild the index if it is enabled, after a bulk change to the nodes 
+    fn reindex(&mut self) {
+        if self.index.is_some() {
+            self.build_index();
+        }
+    }
+
+
+    /// The node with this id, whatever its variant. O(1) if the graph was created with_index, otherwise a scan of the variants 
+    pub fn get_node_by_id(&self, id: &str) -> Option<&serde_json::Value> {
+        match &self.index {
+            Some(index) => self.nodes.get(index.get(id)?)?.get(id),
+            None => self.nodes.values().find_map(|collection| collection.get(id)),
+        }
+    }
+
+
+    /// Register the style the frontend should apply to every node of variant, replacing any previous style for it 
+    pub fn set_variant_style(&mut self, variant: &str, style: serde_json::Value) {
+        self.meta.variant_styles.insert(variant.to_string(), style);
+    }
+
+
+    /// Run f on the JSON of every node added from now on, e.g. to stamp a tenant id or a color in one place.
+    /// It runs at insertion through add_node, add_node_from and the source_edge_target methods, and replaces any earlier
+    /// decorator. Nodes already in the graph, or put there by editing the nodes map directly, merge, node_entry etc.,
+    /// are not decorated until decorate_all_now is called. f must be Send + Sync so the Graph can still cross threads 
+    pub fn with_node_decorator(&mut self, f: impl Fn(&mut serde_json::Value) + Send + Sync + 'static) {
+        self.decorator = Some(Box::new(f));
+    }
+
+
+    /// Run the node decorator, if one is set, on every node currently in the graph 
+    pub fn decorate_all_now(&mut self) {
+        self.invalidate_cache();
+        if let Some(decorate) = &self.decorator {
+            for node in self.nodes.values_mut().flat_map(|collection| collection.values_mut()) {
+                decorate(node);
+            }
+        }
+    }
+
+
+    /// Seed the frontend's layout so screenshots are reproducible across reloads 
+    pub fn set_layout_seed(&mut self, seed: u64) {
+        self.layout_seed = Some(seed);
+    }
+
+
+    /// Build a graph by consuming an async stream of ToNodeJSON items, without first collecting them into a Vec 
+    #[cfg(feature = "tokio")]
+    pub async fn from_stream<S, N, NV, PK, T>(stream: S) -> Result<Graph, serde_json::Error> where 
+        S: futures_util::Stream<Item = N>,
+        N: ToNodeJSON<NV, PK, T>,
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        use futures_util::StreamExt;
+        let mut graph = Graph::new();
+        futures_util::pin_mut!(stream);
+        while let Some(n) = stream.next().await {
+            graph.add_node_from(&n)?;
+        }
+        Ok(graph)
+    }
+
+
+    /// Same as from_stream, but calls progress with the running count after every `every` inserts, e.g. to drive a progress bar 
+    #[cfg(feature = "tokio")]
+    pub async fn from_stream_with_progress<S, N, NV, PK, T>(stream: S, every: usize, mut progress: impl FnMut(usize)) -> Result<Graph, serde_json::Error> where 
+        S: futures_util::Stream<Item = N>,
+        N: ToNodeJSON<NV, PK, T>,
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        use futures_util::StreamExt;
+        let every = every.max(1);
+        let mut graph = Graph::new();
+        let mut count = 0;
+        futures_util::pin_mut!(stream);
+        while let Some(n) = stream.next().await {
+            graph.add_node_from(&n)?;
+            count += 1;
+            if count % every == 0 {
+                progress(count);
+            }
+        }
+        Ok(graph)
+    }
+
+
+    /// Build a graph from any iterator of ToNodeJSON items 
+    pub fn from_nodes<I, N, NV, PK, T>(nodes: I) -> Result<Graph, serde_json::Error> where 
+        I: IntoIterator<Item = N>,
+        N: ToNodeJSON<NV, PK, T>,
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        let mut graph = Graph::new();
+        for n in nodes {
+            graph.add_node_from(&n)?;
+        }
+        Ok(graph)
+    }
+
+
+    /// Build a graph from a flat parent/child table of (id, parent id, name) rows, e.g. a category tree.
+    /// Each row becomes a node_variant node keyed by its id as given, and each row with a parent gets an
+    /// edge_variant edge from it to the parent ("child-of"); roots (a None parent) get no edge.
+    /// Edge ids are built as source_edge_target builds them from the two ids as pks, e.g. "\"a1\"|child-of|\"a\"",
+    /// so the same link added either way is one edge.
+    /// GraphError::DanglingEdges is returned if any parent id has no row of its own 
+    pub fn from_tree<I: IntoIterator<Item = (String, Option<String>, String)>>(rows: I, node_variant: &str, edge_variant: &str) -> Result<Graph, GraphError> {
+        let mut graph = Graph::new();
+        for (id, parent, name) in rows {
+            let node = Node::new(node_variant, id.as_str(), id.clone(), name, ());
+            graph.add_node(&node)?;
+            if let Some(parent) = parent {
+                let edge = Edge{
+                    variant: edge_variant,
+                    variant_pk: (id.as_str(), parent.as_str()),
+                    id: format!("{:?}|{}|{:?}", id, edge_variant, parent),
+                    source: id.clone(),
+                    target: parent.clone(),
+                    props: (),
+                    valid_from: None,
+                    valid_to: None,
+                    label: Some(edge_variant.to_string()),
+                    bundle: None,
+                    directed: None,
+                };
+                graph.add_edge(&edge)?;
+            }
+        }
+        graph.validate()?;
+        Ok(graph)
+    }
+
+
+    /// Same as from_nodes, but calls progress with the running count after every `every` inserts, e.g. to drive a progress bar.
+    /// An every of 0 is treated as 1 
+    pub fn from_nodes_with_progress<I, N, NV, PK, T>(nodes: I, every: usize, mut progress: impl FnMut(usize)) -> Result<Graph, serde_json::Error> where 
+        I: IntoIterator<Item = N>,
+        N: ToNodeJSON<NV, PK, T>,
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        let every = every.max(1);
+        let mut graph = Graph::new();
+        for (i, n) in nodes.into_iter().enumerate() {
+            graph.add_node_from(&n)?;
+            if (i + 1) % every == 0 {
+                progress(i + 1);
+            }
+        }
+        Ok(graph)
+    }
+
+
+    pub fn add_node_from<NV, PK, T>(&mut self, n : &dyn ToNodeJSON<NV, PK, T>) -> Result<Node<NV, PK, T>, serde_json::Error> where 
         NV: Serialize + fmt::Display,
         PK: Serialize + fmt::Debug, 
         T:  Serialize  
@@ -217,13 +899,32 @@ impl Graph
         let json = edge.to_edge_json()?;
         let collection = edge.variant.to_string();
         let id = edge.id.clone();
+        self.touch_edge(&collection, &id);
         let _x = self.edges
             .entry(collection)
-            .or_insert(HashMap::new())
+            .or_default()
             .insert(id, json);
         Ok(())
     }
 
+    /// Add a hyperedge. Every member must already be a node in the graph, otherwise
+    /// GraphError::NodeNotFound is returned for the first missing member and nothing is added 
+    pub fn add_hyperedge<EV, PK, T>(&mut self, hyperedge: &HyperEdge<EV, PK, T>) -> Result<(), GraphError> where 
+        EV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        if let Some(missing) = hyperedge.members.iter().find(|id| !self.contains_node(id)) {
+            return Err(GraphError::NodeNotFound(missing.clone()));
+        }
+        let json = hyperedge.to_hyperedge_json()?;
+        self.hyperedges
+            .entry(hyperedge.variant.to_string())
+            .or_default()
+            .insert(hyperedge.id.clone(), json);
+        Ok(())
+    }
+
     /*// by making this method private, the user must use source_edge_target() etc., ensuring the nodes that go with the edge are populated 
     fn add_edge_from<EV, PK, T>(&mut self, e : &(dyn ToEdgeJSON<EV, PK, T>)) -> Result<Edge<EV, PK, T>, serde_json::Error> where 
         EV: Serialize + fmt::Display,
@@ -236,7 +937,12 @@ impl Graph
     }*/
 
     
-    pub fn source_edge_target<NVS, PKS, TS, EV, ET, NVT, PKT, TT>(&mut self, n_source: &(dyn ToNode<NVS, PKS, TS>), n_target: &(dyn ToNode<NVT, PKT, TT>), edge_variant: EV, edge_props: ET)
+    /// Add the source and target nodes along with an edge of edge_variant from source to target.
+    /// The edge id is built from the two node pks and the edge variant, so calling this again for the same
+    /// (source, target, edge_variant) overwrites the earlier edge. If the same pair legitimately has several
+    /// edges of one variant, use source_edge_target_with_pk instead 
+    #[allow(clippy::type_complexity)]
+    pub fn source_edge_target<NVS, PKS, TS, EV, ET, NVT, PKT, TT>(&mut self, n_source: &dyn ToNode<NVS, PKS, TS>, n_target: &dyn ToNode<NVT, PKT, TT>, edge_variant: EV, edge_props: ET)
         -> Result<(Node<NVS, PKS, TS>, Edge<EV, (PKS, PKT), ET>, Node<NVT, PKT, TT>), serde_json::Error> 
     where 
         NVS: Serialize + fmt::Display,      // Node Variant, Source
@@ -251,6 +957,7 @@ impl Graph
         let source = n_source.to_node();
         let target = n_target.to_node();
         let id = format!("{:?}|{}|{:?}", &n_source.node_pk(), &edge_variant, &n_target.node_pk());
+        let label = Some(edge_variant.to_string());
         let edge: Edge<EV, (PKS, PKT), ET>  = Edge{
             variant: edge_variant,
             variant_pk: (n_source.node_pk(), n_target.node_pk()),
@@ -258,43 +965,2555 @@ impl Graph
             source: n_source.node_id(),
             target: n_target.node_id(),
             props: edge_props,
+            valid_from: None,
+            valid_to: None,
+            label,
+            bundle: None,
+            directed: None,
         };
         self.add_node(&source)?;
         self.add_edge(&edge)?;
         self.add_node(&target)?;
         Ok((source, edge, target))
     }
-}
 
 
+    /// Like source_edge_target, but edge_pk is appended to the edge id (and variant_pk) so that several edges of the
+    /// same variant between the same two nodes can coexist, e.g. two separate citations of one article by another 
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
+    pub fn source_edge_target_with_pk<NVS, PKS, TS, EV, EPK, ET, NVT, PKT, TT>(&mut self, n_source: &dyn ToNode<NVS, PKS, TS>, n_target: &dyn ToNode<NVT, PKT, TT>, edge_variant: EV, edge_pk: EPK, edge_props: ET)
+        -> Result<(Node<NVS, PKS, TS>, Edge<EV, (PKS, PKT, EPK), ET>, Node<NVT, PKT, TT>), serde_json::Error> 
+    where 
+        NVS: Serialize + fmt::Display,      // Node Variant, Source
+        PKS: Serialize + fmt::Debug,        // Primary Key, Source
+        TS:  Serialize,                     // property Type, Source
+        EV: Serialize + fmt::Display,       // Edge Variant
+        EPK: Serialize + fmt::Debug,        // Edge Primary Key
+        ET:  Serialize,                     // Edge property Type
+        NVT: Serialize + fmt::Display,      // Node Variant, Target
+        PKT: Serialize + fmt::Debug,        // Primary Key, Target
+        TT:  Serialize,                     // property Type, Target 
+    {
+        let source = n_source.to_node();
+        let target = n_target.to_node();
+        let id = format!("{:?}|{}|{:?}|{:?}", &n_source.node_pk(), &edge_variant, &n_target.node_pk(), &edge_pk);
+        let label = Some(edge_variant.to_string());
+        let edge: Edge<EV, (PKS, PKT, EPK), ET>  = Edge{
+            variant: edge_variant,
+            variant_pk: (n_source.node_pk(), n_target.node_pk(), edge_pk),
+            id, 
+            source: n_source.node_id(),
+            target: n_target.node_id(),
+            props: edge_props,
+            valid_from: None,
+            valid_to: None,
+            label,
+            bundle: None,
+            directed: None,
+        };
+        self.add_node(&source)?;
+        self.add_edge(&edge)?;
+        self.add_node(&target)?;
+        Ok((source, edge, target))
+    }
 
 
-/// this trait simply means you define an implementation of to_graph, which must return a Graph struct 
-pub trait ToGraph {
-    /// Define how an existing graph should have content added to it from this struct 
-    fn mut_graph(&self, graph: &mut Graph) -> Result<(), serde_json::Error>;
-    fn to_graph(&self) -> Result<Graph, serde_json::Error> {
-        let mut g = Graph::new();
-        self.mut_graph(&mut g)?;
-        Ok(g)
+    /// true if a node with this id exists, whatever its variant 
+    pub fn contains_node(&self, id: &str) -> bool {
+        self.nodes.values().any(|collection| collection.contains_key(id))
     }
-}
 
 
-/// If a struct implements ToGraph, you may wish for it to also implement FocusNode.  
-/// The idea here is that if you display a (sub)graph for the user to see,
-/// you may wish to zoom to a particular node as the 'starting point'
-/// or other node of interest. 
-/// The ZoomNode trait allows that functionality by providing a variant and variant_pk,
-/// much like the ToNode trait 
-pub trait ZoomNode<EV: fmt::Display, PK: fmt::Debug>: ToGraph {
-    /// EV is the variant, PK is the variant_pk
-    fn zoom_to(&self) -> Option<(EV, PK)>;
-    /// the graph3d.js :: zoomToId() function expects a node id = 'variant|PK'
-    fn zoom_to_id(&self) -> Option<String> {
-        match self.zoom_to() {
-            Some((variant, pk)) => Some(format!("{}|{:?}", variant, pk)),
-            None => None,
+    /// Remove the node with this id (whatever its variant) along with every edge that has it as source or target,
+    /// and drop it from the members of any hyperedge.
+    /// Any node, edge or hyperedge variant left with an empty collection is removed too, so the variant keys
+    /// of a graph always correspond to variants that are actually present.
+    /// Returns the removed node JSON if it was present 
+    pub fn remove_node(&mut self, id: &str) -> Option<serde_json::Value> {
+        let node = self.nodes.values_mut().find_map(|collection| collection.remove(id))?;
+        if let Some(index) = self.index.as_mut() {
+            index.remove(id);
+        }
+        for collection in self.edges.values_mut() {
+            collection.retain(|_, edge| {
+                let (source, target) = edge_ends(edge);
+                source != id && target != id
+            });
         }
+        self.retain_members(|member| member != id);
+        self.drop_empty_collections();
+        Some(node)
     }
-}
\ No newline at end of file
+
+
+    /// remove any node, edge or hyperedge variant whose collection is empty 
+    fn drop_empty_collections(&mut self) {
+        self.nodes.retain(|_, collection| !collection.is_empty());
+        self.edges.retain(|_, collection| !collection.is_empty());
+        self.hyperedges.retain(|_, collection| !collection.is_empty());
+    }
+
+
+    /// keep only the hyperedge members for which keep(member id) is true, removing hyperedges left without members 
+    fn retain_members<F: Fn(&str) -> bool>(&mut self, keep: F) {
+        for collection in self.hyperedges.values_mut() {
+            collection.retain(|_id, hyperedge| match hyperedge.get_mut("members").and_then(|members| members.as_array_mut()) {
+                Some(members) => {
+                    members.retain(|member| member.as_str().is_some_and(&keep));
+                    !members.is_empty()
+                },
+                None => true,
+            });
+        }
+    }
+
+
+    /// Release excess capacity held by the node, edge and hyperedge maps, dropping empty variant collections entirely.
+    /// Useful for long-lived processes after heavily pruning a graph 
+    pub fn shrink_to_fit(&mut self) {
+        self.drop_empty_collections();
+        for collection in self.nodes.values_mut().chain(self.edges.values_mut()).chain(self.hyperedges.values_mut()) {
+            collection.shrink_to_fit();
+        }
+        self.nodes.shrink_to_fit();
+        self.edges.shrink_to_fit();
+        self.hyperedges.shrink_to_fit();
+    }
+
+
+    /// Collapse the node remove_id into the node into_id: every edge touching remove_id is rerouted to into_id,
+    /// edges that thereby become self-loops are dropped, hyperedges with remove_id as a member get into_id instead,
+    /// and remove_id is removed.
+    /// Edge ids are left unchanged, so a rerouted edge keeps the id it was created with 
+    pub fn contract_node(&mut self, remove_id: &str, into_id: &str) -> Result<(), GraphError> {
+        for id in [remove_id, into_id] {
+            if !self.contains_node(id) {
+                return Err(GraphError::NodeNotFound(id.to_string()));
+            }
+        }
+        if remove_id == into_id {
+            return Ok(());
+        }
+        self.reroute_edges(&HashMap::from([(remove_id.to_string(), into_id.to_string())]));
+        self.remove_node(remove_id);
+        Ok(())
+    }
+
+
+    /// point every edge endpoint and hyperedge member that is a key of reroutes at the corresponding value instead,
+    /// dropping edges that become self-loops and members that become duplicates as a result 
+    fn reroute_edges(&mut self, reroutes: &HashMap<String, String>) {
+        self.invalidate_cache();
+        for hyperedge in self.hyperedges.values_mut().flat_map(|collection| collection.values_mut()) {
+            if let Some(members) = hyperedge.get_mut("members").and_then(|members| members.as_array_mut()) {
+                for member in members.iter_mut() {
+                    if let Some(into_id) = member.as_str().and_then(|id| reroutes.get(id)) {
+                        *member = into_id.as_str().into();
+                    }
+                }
+                let mut seen = HashSet::new();
+                members.retain(|member| seen.insert(member.clone()));
+            }
+        }
+        for collection in self.edges.values_mut() {
+            collection.retain(|_, edge| {
+                let mut rerouted = false;
+                for end in ["source", "target"] {
+                    if let Some(into_id) = edge[end].as_str().and_then(|id| reroutes.get(id)) {
+                        edge[end] = into_id.as_str().into();
+                        rerouted = true;
+                    }
+                }
+                let (source, target) = edge_ends(edge);
+                !(rerouted && source == target)
+            });
+        }
+    }
+
+
+    /// Entity resolution: collapse nodes whose key_fn(node JSON) is equal into one canonical node (the one with the
+    /// smallest id), rerouting the duplicates' edges and hyperedge memberships to it and dropping edges that become
+    /// self-loops as a result.
+    /// Nodes of different variants can be merged if their keys match 
+    pub fn dedupe_by<F: Fn(&serde_json::Value) -> String>(&mut self, key_fn: F) {
+        let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+        for (id, node) in self.nodes.values().flat_map(|collection| collection.iter()) {
+            groups.entry(key_fn(node)).or_default().push(id.clone());
+        }
+        let mut reroutes: HashMap<String, String> = HashMap::new();
+        for mut ids in groups.into_values() {
+            ids.sort();
+            for duplicate in &ids[1..] {
+                reroutes.insert(duplicate.clone(), ids[0].clone());
+            }
+        }
+        self.reroute_edges(&reroutes);
+        for collection in self.nodes.values_mut() {
+            collection.retain(|id, _node| !reroutes.contains_key(id));
+        }
+        self.drop_empty_collections();
+        self.reindex();
+    }
+
+
+    /// The distinct (source variant, target variant) pairs connected by edges of edge_variant,
+    /// e.g. telling you "cites" edges go from Article to Article and "wrote" edges from Author to Article.
+    /// The variants are parsed from the "variant|pk" prefix of the node ids 
+    pub fn edge_variant_endpoints(&self, edge_variant: &str) -> HashSet<(String, String)> {
+        self.edges.get(edge_variant)
+            .map(|collection| collection.values()
+                .map(|edge| {
+                    let (source, target) = edge_ends(edge);
+                    (variant_of_id(source).to_string(), variant_of_id(target).to_string())
+                })
+                .collect())
+            .unwrap_or_default()
+    }
+
+
+    /// Move every node and edge of other into self. On an id collision the node or edge from other wins 
+    pub fn merge(&mut self, other: Graph) {
+        for (variant, collection) in other.nodes {
+            for id in collection.keys() {
+                self.touch_node(&variant, id);
+            }
+            self.nodes.entry(variant).or_default().extend(collection);
+        }
+        self.merge_edges(other.edges);
+        for (variant, collection) in other.hyperedges {
+            self.hyperedges.entry(variant).or_default().extend(collection);
+        }
+        self.reindex();
+    }
+
+
+    /// move the edges into self, the moved edge winning on an id collision 
+    fn merge_edges(&mut self, edges: HashMap<String, HashMap<String, serde_json::Value>>) {
+        for (variant, collection) in edges {
+            for id in collection.keys() {
+                self.touch_edge(&variant, id);
+            }
+            self.edges.entry(variant).or_default().extend(collection);
+        }
+    }
+
+
+    /// Like merge, but on a node id collision the node with the larger updated_at is kept, so merging incremental
+    /// updates in any order gives the same result. When the timestamps are equal or either is absent the node from
+    /// other wins, as in merge. Edges and hyperedges are merged as in merge 
+    pub fn merge_newest(&mut self, other: Graph) {
+        let updated_at = |node: &serde_json::Value| node.get("updated_at").and_then(serde_json::Value::as_i64);
+        let mut replaced = Vec::new();
+        for (variant, collection) in other.nodes {
+            let existing = self.nodes.entry(variant.clone()).or_default();
+            for (id, node) in collection {
+                let keep_existing = existing.get(&id)
+                    .and_then(updated_at)
+                    .zip(updated_at(&node))
+                    .is_some_and(|(old, new)| old > new);
+                if !keep_existing {
+                    replaced.push((variant.clone(), id.clone()));
+                    existing.insert(id, node);
+                }
+            }
+        }
+        for (variant, id) in replaced {
+            self.touch_node(&variant, &id);
+        }
+        self.merge_edges(other.edges);
+        for (variant, collection) in other.hyperedges {
+            self.hyperedges.entry(variant).or_default().extend(collection);
+        }
+        self.reindex();
+    }
+
+
+    /// Merge many graphs into one, e.g. the Vec<Graph> produced by a parallel map.
+    /// Graphs are merged in iteration order, so on an id collision the last one wins 
+    pub fn union_all<I: IntoIterator<Item = Graph>>(graphs: I) -> Graph {
+        let mut union = Graph::new();
+        for graph in graphs {
+            union.merge(graph);
+        }
+        union
+    }
+
+
+    /// The ids of the edges (of any variant) connecting a_id and b_id, sorted.
+    /// If directed is true only edges from a_id to b_id count, otherwise edges in either direction do 
+    pub fn edges_between(&self, a_id: &str, b_id: &str, directed: bool) -> Vec<String> {
+        let mut ids: Vec<String> = self.edges.values()
+            .flat_map(|collection| collection.iter())
+            .filter(|(_id, edge)| {
+                let (source, target) = edge_ends(edge);
+                (source == a_id && target == b_id) || (!directed && source == b_id && target == a_id)
+            })
+            .map(|(id, _edge)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// Merge parallel edges: of all the edges of one variant from the same source to the same target, keep the one with
+    /// the smallest id and set its top-level "weight" key to how many there were. Every remaining edge gets a weight,
+    /// 1 if it had no parallels. Edges in opposite directions are not parallel 
+    pub fn collapse_parallel_edges(&mut self) {
+        self.invalidate_cache();
+        for collection in self.edges.values_mut() {
+            let mut groups: HashMap<(String, String), Vec<String>> = HashMap::new();
+            for (id, edge) in collection.iter() {
+                let (source, target) = edge_ends(edge);
+                groups.entry((source.to_string(), target.to_string())).or_default().push(id.clone());
+            }
+            for mut ids in groups.into_values() {
+                ids.sort();
+                for duplicate in &ids[1..] {
+                    collection.remove(duplicate);
+                }
+                if let Some(edge) = collection.get_mut(&ids[0]).and_then(serde_json::Value::as_object_mut) {
+                    edge.insert("weight".to_string(), ids.len().into());
+                }
+            }
+        }
+    }
+
+
+    /// Every pair of nodes of the given variant with no edge between them in either direction, as (smaller id, larger id)
+    /// sorted, i.e. the complement graph of that variant. This is O(n²) in the variant's size, so at most
+    /// MISSING_EDGES_LIMIT pairs are returned: the first ones in sorted order 
+    pub fn missing_edges(&self, variant: &str) -> Vec<(String, String)> {
+        let Some(collection) = self.nodes.get(variant) else {
+            return Vec::new();
+        };
+        let mut ids: Vec<&str> = collection.keys().map(String::as_str).collect();
+        ids.sort();
+        let connected: HashSet<(&str, &str)> = self.edges.values()
+            .flat_map(|collection| collection.values())
+            .map(|edge| {
+                let (source, target) = edge_ends(edge);
+                if source < target { (source, target) } else { (target, source) }
+            })
+            .collect();
+        let mut missing = Vec::new();
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                if missing.len() == MISSING_EDGES_LIMIT {
+                    return missing;
+                }
+                if !connected.contains(&(*a, *b)) {
+                    missing.push((a.to_string(), b.to_string()));
+                }
+            }
+        }
+        missing
+    }
+
+
+    /// The graph as it was at epoch millis t: only edges whose valid_from <= t < valid_to are kept
+    /// (a missing valid_from or valid_to leaves that end of the window open).
+    /// If keep_all_nodes is false, only nodes touched by a surviving edge are kept; otherwise every node is.
+    /// Hyperedges whose members are all kept carry over, as do meta and layout_seed 
+    pub fn at_time(&self, t: i64, keep_all_nodes: bool) -> Graph {
+        let mut graph = self.filter_edges(|_variant, _id, edge| {
+            let started = edge["valid_from"].as_i64().is_none_or(|from| from <= t);
+            let ended = edge["valid_to"].as_i64().is_some_and(|to| to <= t);
+            started && !ended
+        });
+        if keep_all_nodes {
+            graph.nodes = self.nodes.clone();
+            self.carry_graph_state(&mut graph);
+        }
+        graph
+    }
+
+
+    /// A new graph holding only the edges for which f(variant, id, edge JSON) is true, plus the nodes they touch
+    /// and the hyperedges whose members are all among those nodes. Meta and layout_seed carry over too 
+    pub fn filter_edges<F: FnMut(&str, &str, &serde_json::Value) -> bool>(&self, mut f: F) -> Graph {
+        let mut graph = Graph::new();
+        let mut touched: HashSet<&str> = HashSet::new();
+        for (variant, collection) in &self.edges {
+            for (id, edge) in collection {
+                if f(variant, id, edge) {
+                    let (source, target) = edge_ends(edge);
+                    touched.insert(source);
+                    touched.insert(target);
+                    graph.edges.entry(variant.clone()).or_default().insert(id.clone(), edge.clone());
+                }
+            }
+        }
+        for (variant, collection) in &self.nodes {
+            for (id, node) in collection {
+                if touched.contains(id.as_str()) {
+                    graph.nodes.entry(variant.clone()).or_default().insert(id.clone(), node.clone());
+                }
+            }
+        }
+        self.carry_graph_state(&mut graph);
+        graph
+    }
+
+
+    /// An entry for the node id within variant, for read-or-insert patterns without a separate contains check.
+    /// Note the variant collection is created if it did not exist, even if nothing is then inserted 
+    pub fn node_entry(&mut self, variant: &str, id: &str) -> NodeEntry<'_> {
+        // the caller may edit the node through the entry 
+        self.touch_node(variant, id);
+        let entry = self.nodes.entry(variant.to_string()).or_default().entry(id.to_string());
+        let index = self.index.as_mut().map(|index| (index, variant.to_string()));
+        NodeEntry{entry, index}
+    }
+
+
+    /// The ids of hyperedges with a member that is not a node in the graph, sorted 
+    pub fn dangling_hyperedges(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.hyperedges.values()
+            .flat_map(|collection| collection.iter())
+            .filter(|(_id, hyperedge)| match hyperedge["members"].as_array() {
+                Some(members) => members.iter().any(|member| !member.as_str().is_some_and(|id| self.contains_node(id))),
+                None => false,
+            })
+            .map(|(id, _hyperedge)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// The ids of edges whose source or target node is not in the graph, sorted 
+    pub fn dangling_edges(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.edges.values()
+            .flat_map(|collection| collection.iter())
+            .filter(|(_id, edge)| {
+                let (source, target) = edge_ends(edge);
+                !self.contains_node(source) || !self.contains_node(target)
+            })
+            .map(|(id, _edge)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// The ids of edges whose source is their target, sorted. Useful on graphs deserialized from elsewhere,
+    /// which did not go through source_edge_target 
+    pub fn self_loops(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.edges.values()
+            .flat_map(|collection| collection.iter())
+            .filter(|(_id, edge)| {
+                let (source, target) = edge_ends(edge);
+                source == target
+            })
+            .map(|(id, _edge)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// Remove every edge whose source is its target, returning how many were removed 
+    pub fn remove_self_loops(&mut self) -> usize {
+        let mut removed = 0;
+        for collection in self.edges.values_mut() {
+            let before = collection.len();
+            collection.retain(|_id, edge| {
+                let (source, target) = edge_ends(edge);
+                source != target
+            });
+            removed += before - collection.len();
+        }
+        self.drop_empty_collections();
+        removed
+    }
+
+
+    /// Rename the node variant from to to, e.g. after renaming a variant of the NV enum, so graphs serialized with the
+    /// old name keep working. The nodes are merged into to if it already exists, replacing any with the same id 
+    pub fn rename_node_variant(&mut self, from: &str, to: &str) {
+        rename_variant(&mut self.nodes, from, to);
+        self.invalidate_cache();
+        self.reindex();
+    }
+
+
+    /// Rename the edge variant from to to, merging into to as rename_node_variant does 
+    pub fn rename_edge_variant(&mut self, from: &str, to: &str) {
+        rename_variant(&mut self.edges, from, to);
+        self.invalidate_cache();
+    }
+
+
+    /// The ids of nodes whose name is empty, missing or not a string, sorted 
+    pub fn nodes_missing_names(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.nodes.values()
+            .flat_map(|collection| collection.iter())
+            .filter(|(_id, node)| node["name"].as_str().is_none_or(str::is_empty))
+            .map(|(id, _node)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// Check every node has a non-empty name before rendering, since the frontend crashes on empty ones 
+    pub fn assert_all_named(&self) -> Result<(), GraphError> {
+        let unnamed = self.nodes_missing_names();
+        if unnamed.is_empty() {
+            Ok(())
+        } else {
+            Err(GraphError::UnnamedNodes(unnamed))
+        }
+    }
+
+
+    /// Replace every non-finite number (NaN or ±infinity) in the nodes, edges and hyperedges with null,
+    /// returning how many were replaced, since neither JSON nor the frontend can represent them.
+    /// Converting props to JSON with this crate already turns NaN and infinite f64s into null, and plain serde_json
+    /// rejects out-of-range literals, so this usually finds nothing; it guards builds where serde_json's
+    /// arbitrary_precision feature is enabled (by this crate's feature of that name or by another crate),
+    /// which lets a literal like 1e999 into a Value 
+    pub fn sanitize(&mut self) -> usize {
+        let replaced = [&mut self.nodes, &mut self.edges, &mut self.hyperedges].into_iter()
+            .flat_map(|collections| collections.values_mut())
+            .flat_map(|collection| collection.values_mut())
+            .map(null_non_finite)
+            .sum();
+        if replaced > 0 {
+            self.invalidate_cache();
+        }
+        replaced
+    }
+
+
+    /// The ids present under more than one node variant, sorted. Edges refer to nodes by id alone,
+    /// so such an edge is ambiguous about which of the nodes it connects 
+    pub fn id_collisions_across_variants(&self) -> Vec<String> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for id in self.nodes.values().flat_map(|collection| collection.keys()) {
+            *counts.entry(id).or_default() += 1;
+        }
+        let mut ids: Vec<String> = counts.into_iter()
+            .filter(|(_id, count)| *count > 1)
+            .map(|(id, _count)| id.to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+
+    /// Check the graph is structurally sound, i.e. every edge connects two nodes that are present
+    /// and every hyperedge member is a node that is present. Dangling edges are reported first 
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let dangling = self.dangling_edges();
+        if !dangling.is_empty() {
+            return Err(GraphError::DanglingEdges(dangling));
+        }
+        let dangling = self.dangling_hyperedges();
+        if dangling.is_empty() {
+            Ok(())
+        } else {
+            Err(GraphError::DanglingHyperedges(dangling))
+        }
+    }
+
+
+    /// Deserialize a graph straight from a reader such as an HTTP body, without first reading it into a String 
+    pub fn from_reader<R: std::io::Read>(reader: R) -> Result<Graph, serde_json::Error> {
+        serde_json::from_reader(reader)
+    }
+
+
+    /// Deserialize a graph from JSON and validate it, so broken graphs from untrusted sources are rejected up front 
+    pub fn from_json_validated(s: &str) -> Result<Graph, GraphError> {
+        let graph: Graph = serde_json::from_str(s)?;
+        graph.validate()?;
+        Ok(graph)
+    }
+
+
+    /// remove every edge whose source or target node is no longer in the graph,
+    /// and every hyperedge member that is no longer in the graph 
+    fn drop_dangling_edges(&mut self) {
+        let ids: HashSet<String> = self.nodes.values().flat_map(|collection| collection.keys().cloned()).collect();
+        for collection in self.edges.values_mut() {
+            collection.retain(|_id, edge| {
+                let (source, target) = edge_ends(edge);
+                ids.contains(source) && ids.contains(target)
+            });
+        }
+        self.retain_members(|member| ids.contains(member));
+        self.drop_empty_collections();
+    }
+
+
+    /// Trim each node variant to at most max_per_variant nodes, so a preview still shows every variant.
+    /// The nodes kept are the first by sorted id, and edges and hyperedge members left dangling are removed 
+    pub fn cap_per_variant(&mut self, max_per_variant: usize) {
+        for collection in self.nodes.values_mut() {
+            if collection.len() > max_per_variant {
+                let mut ids: Vec<String> = collection.keys().cloned().collect();
+                ids.sort();
+                for id in &ids[max_per_variant..] {
+                    collection.remove(id);
+                }
+            }
+        }
+        self.drop_dangling_edges();
+        self.reindex();
+    }
+
+
+    /// Drop edges so that no node is an endpoint of more than max_degree of them (counted as in degrees),
+    /// keeping hubs from dominating the layout. Edges are considered in order of sorted id (then variant) and
+    /// kept while both of their endpoints are under the cap, so each hub keeps its first max_degree edges.
+    /// This changes connectivity: paths through the dropped edges are lost, nodes may become disconnected,
+    /// and a node can end up below the cap because its other edges went to nodes that reached it first 
+    pub fn cap_node_degree(&mut self, max_degree: usize) {
+        let mut keyed: Vec<(String, String)> = self.edges.iter()
+            .flat_map(|(variant, collection)| collection.keys().map(move |id| (id.clone(), variant.clone())))
+            .collect();
+        keyed.sort();
+        let mut degrees: HashMap<String, usize> = HashMap::new();
+        for (id, variant) in keyed {
+            let collection = self.edges.get_mut(&variant).expect("variant was just listed");
+            let (source, target) = edge_ends(&collection[&id]);
+            let (source, target) = (source.to_string(), target.to_string());
+            let source_degree = degrees.get(&source).copied().unwrap_or_default();
+            let target_degree = degrees.get(&target).copied().unwrap_or_default();
+            let fits = if source == target {
+                source_degree + 2 <= max_degree
+            } else {
+                source_degree < max_degree && target_degree < max_degree
+            };
+            if fits {
+                *degrees.entry(source).or_default() += 1;
+                *degrees.entry(target).or_default() += 1;
+            } else {
+                collection.remove(&id);
+            }
+        }
+        self.drop_empty_collections();
+    }
+
+
+    /// Rewrite every id in the graph through f, e.g. to hash ids before exporting an anonymized graph.
+    /// Node keys and their "id" fields, edge "source" and "target" fields, edge keys and their "id" fields,
+    /// hyperedge keys, "id" fields and "members", and meta.zoom_id are all mapped. f is called once per distinct id
+    /// and must be injective: if two ids map to the same value the graph silently loses the nodes or edges that collide 
+    pub fn map_ids<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+        let mut mapped: HashMap<String, String> = HashMap::new();
+        let mut map = |id: &str| -> String {
+            mapped.entry(id.to_string()).or_insert_with(|| f(id)).clone()
+        };
+        for collection in self.nodes.values_mut() {
+            *collection = collection.drain()
+                .map(|(id, mut node)| {
+                    let new_id = map(&id);
+                    if let Some(node) = node.as_object_mut() {
+                        node.insert("id".to_string(), new_id.as_str().into());
+                    }
+                    (new_id, node)
+                })
+                .collect();
+        }
+        for collection in self.edges.values_mut() {
+            *collection = collection.drain()
+                .map(|(id, mut edge)| {
+                    let new_id = map(&id);
+                    if edge.is_object() {
+                        let (source, target) = edge_ends(&edge);
+                        let (source, target) = (map(source), map(target));
+                        if let Some(edge) = edge.as_object_mut() {
+                            edge.insert("source".to_string(), source.into());
+                            edge.insert("target".to_string(), target.into());
+                            edge.insert("id".to_string(), new_id.as_str().into());
+                        }
+                    }
+                    (new_id, edge)
+                })
+                .collect();
+        }
+        for collection in self.hyperedges.values_mut() {
+            *collection = collection.drain()
+                .map(|(id, mut hyperedge)| {
+                    if let Some(members) = hyperedge.get_mut("members").and_then(|members| members.as_array_mut()) {
+                        for member in members.iter_mut() {
+                            if let Some(member_id) = member.as_str() {
+                                *member = map(member_id).into();
+                            }
+                        }
+                    }
+                    let new_id = map(&id);
+                    if let Some(hyperedge) = hyperedge.as_object_mut() {
+                        hyperedge.insert("id".to_string(), new_id.as_str().into());
+                    }
+                    (new_id, hyperedge)
+                })
+                .collect();
+        }
+        if let Some(zoom_id) = self.meta.zoom_id.as_mut() {
+            *zoom_id = map(zoom_id);
+        }
+        self.invalidate_cache();
+        self.reindex();
+    }
+
+
+    /// Namespace the graph before merging it with graphs from another system that may reuse the same ids:
+    /// prefix is prepended verbatim (so include any separator, e.g. "crm:") to every node, edge and hyperedge id,
+    /// and edge endpoints, hyperedge members and meta.zoom_id are rewritten to match, as in map_ids 
+    pub fn prefix_ids(&mut self, prefix: &str) {
+        self.map_ids(|id| format!("{}{}", prefix, id));
+    }
+
+
+    /// The ids of the nodes adjacent to id, following edges of every variant in either direction, sorted and deduplicated 
+    pub fn neighbors(&self, id: &str) -> Vec<String> {
+        let mut neighbors: Vec<String> = self.edges.values()
+            .flat_map(|collection| collection.values())
+            .filter_map(|edge| match edge_ends(edge) {
+                (source, target) if source == id && target != id => Some(target.to_string()),
+                (source, target) if target == id && source != id => Some(source.to_string()),
+                _ => None,
+            })
+            .collect();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+
+    /// The ids of the nodes adjacent to both a_id and b_id, in either direction, sorted 
+    pub fn common_neighbors(&self, a_id: &str, b_id: &str) -> Vec<String> {
+        let b_neighbors: HashSet<String> = self.neighbors(b_id).into_iter().collect();
+        self.neighbors(a_id).into_iter()
+            .filter(|id| b_neighbors.contains(id))
+            .collect()
+    }
+
+
+    /// |N(a) ∩ N(b)| / |N(a) ∪ N(b)| over the undirected neighbor sets, from 0.0 (nothing shared) to 1.0 (identical
+    /// neighbors), e.g. to rank candidate links. 0.0 when neither node has neighbors 
+    pub fn jaccard_similarity(&self, a_id: &str, b_id: &str) -> f64 {
+        let a_neighbors: HashSet<String> = self.neighbors(a_id).into_iter().collect();
+        let b_neighbors: HashSet<String> = self.neighbors(b_id).into_iter().collect();
+        let union = a_neighbors.union(&b_neighbors).count();
+        if union == 0 {
+            return 0.0;
+        }
+        a_neighbors.intersection(&b_neighbors).count() as f64 / union as f64
+    }
+
+
+    /// The neighbors of each of ids, as neighbors() would return them, computed in a single pass over the edges
+    /// rather than one pass per id 
+    pub fn neighbor_map(&self, ids: &[&str]) -> HashMap<String, Vec<String>> {
+        let mut sets: HashMap<&str, HashSet<&str>> = ids.iter().map(|id| (*id, HashSet::new())).collect();
+        for edge in self.edges.values().flat_map(|collection| collection.values()) {
+            let (source, target) = edge_ends(edge);
+            if source == target {
+                continue;
+            }
+            if let Some(set) = sets.get_mut(source) {
+                set.insert(target);
+            }
+            if let Some(set) = sets.get_mut(target) {
+                set.insert(source);
+            }
+        }
+        sets.into_iter()
+            .map(|(id, set)| {
+                let mut neighbors: Vec<String> = set.into_iter().map(String::from).collect();
+                neighbors.sort();
+                (id.to_string(), neighbors)
+            })
+            .collect()
+    }
+
+
+    /// The ids of the nodes id has an edge to (of any variant), sorted and deduplicated 
+    pub fn out_neighbors(&self, id: &str) -> Vec<String> {
+        self.directed_neighbors(id, true)
+    }
+
+
+    /// The ids of the nodes with an edge (of any variant) to id, sorted and deduplicated 
+    pub fn in_neighbors(&self, id: &str) -> Vec<String> {
+        self.directed_neighbors(id, false)
+    }
+
+
+    fn directed_neighbors(&self, id: &str, outgoing: bool) -> Vec<String> {
+        let mut neighbors: Vec<String> = self.edges.values()
+            .flat_map(|collection| collection.values())
+            .filter_map(|edge| {
+                let (source, target) = edge_ends(edge);
+                let (from, to) = if outgoing { (source, target) } else { (target, source) };
+                (from == id).then(|| to.to_string())
+            })
+            .collect();
+        neighbors.sort();
+        neighbors.dedup();
+        neighbors
+    }
+
+
+    /// Flip the direction of every edge by swapping its source and target.
+    /// Edges made by source_edge_target have their (source pk, target pk, ..) variant_pk swapped to match,
+    /// but edge ids are kept as they are so that anything holding an edge id can still find it:
+    /// note this means an id like "1|link|2" now names the edge from 2 to 1. Edges whose JSON is not an object are skipped 
+    pub fn reverse_edges(&mut self) {
+        self.invalidate_cache();
+        for edge in self.edges.values_mut().flat_map(|collection| collection.values_mut()) {
+            let Some(edge) = edge.as_object_mut() else {
+                continue;
+            };
+            let source = edge.remove("source");
+            let target = edge.remove("target");
+            if let Some(target) = target {
+                edge.insert("source".to_string(), target);
+            }
+            if let Some(source) = source {
+                edge.insert("target".to_string(), source);
+            }
+            if let Some(pk) = edge.get_mut("variant_pk").and_then(|pk| pk.as_array_mut()) {
+                if pk.len() >= 2 {
+                    pk.swap(0, 1);
+                }
+            }
+        }
+    }
+
+
+    /// Add the reverse of every edge that lacks one, so algorithms following edge directions see the stored graph as
+    /// undirected. An edge counts as reversed if the same variant already has an edge from its target to its source.
+    /// Each added edge copies the original with source and target (and a source_edge_target variant_pk) swapped,
+    /// and gets the id "{original id}|reverse". Self-loops are their own reverse, and edges whose JSON is not an
+    /// object are skipped 
+    pub fn symmetrize(&mut self) {
+        let mut added = Vec::new();
+        for (variant, collection) in self.edges.iter_mut() {
+            let ends: HashSet<(String, String)> = collection.values()
+                .map(|edge| {
+                    let (source, target) = edge_ends(edge);
+                    (source.to_string(), target.to_string())
+                })
+                .collect();
+            let mut reversed = Vec::new();
+            for (id, edge) in collection.iter() {
+                let (source, target) = edge_ends(edge);
+                if source == target || ends.contains(&(target.to_string(), source.to_string())) {
+                    continue;
+                }
+                let Some(mut reverse) = edge.as_object().cloned() else {
+                    continue;
+                };
+                let reverse_id = format!("{}|reverse", id);
+                reverse.insert("source".to_string(), target.into());
+                reverse.insert("target".to_string(), source.into());
+                reverse.insert("id".to_string(), reverse_id.as_str().into());
+                if let Some(pk) = reverse.get_mut("variant_pk").and_then(|pk| pk.as_array_mut()) {
+                    if pk.len() >= 2 {
+                        pk.swap(0, 1);
+                    }
+                }
+                added.push((variant.clone(), reverse_id.clone()));
+                reversed.push((reverse_id, reverse.into()));
+            }
+            collection.extend(reversed);
+        }
+        for (variant, id) in added {
+            self.touch_edge(&variant, &id);
+        }
+    }
+
+
+    /// Pin nodes to positions cached from an earlier layout run by writing fx/fy/fz onto each node,
+    /// so the frontend starts from the stable layout rather than re-running the force simulation.
+    /// Nodes without a position, nodes whose JSON is not an object and positions without a node are left alone 
+    pub fn apply_positions(&mut self, positions: &HashMap<String, (f64, f64, f64)>) {
+        let mut pinned = Vec::new();
+        for (variant, collection) in self.nodes.iter_mut() {
+            for (id, node) in collection.iter_mut() {
+                if let (Some((x, y, z)), Some(node)) = (positions.get(id), node.as_object_mut()) {
+                    node.insert("fx".to_string(), (*x).into());
+                    node.insert("fy".to_string(), (*y).into());
+                    node.insert("fz".to_string(), (*z).into());
+                    pinned.push((variant.clone(), id.clone()));
+                }
+            }
+        }
+        for (variant, id) in pinned {
+            self.touch_node(&variant, &id);
+        }
+    }
+
+
+    /// The position of every node that has one, e.g. after the frontend posted its layout back, for caching and later
+    /// apply_positions. A node's pinned fx/fy/fz are used if all three are numbers, otherwise its x/y/z;
+    /// nodes with neither are skipped 
+    pub fn positions(&self) -> HashMap<String, (f64, f64, f64)> {
+        let coordinates = |node: &serde_json::Value, keys: [&str; 3]| {
+            Some((node.get(keys[0])?.as_f64()?, node.get(keys[1])?.as_f64()?, node.get(keys[2])?.as_f64()?))
+        };
+        self.nodes.values()
+            .flat_map(|collection| collection.iter())
+            .filter_map(|(id, node)| {
+                let position = coordinates(node, ["fx", "fy", "fz"]).or_else(|| coordinates(node, ["x", "y", "z"]))?;
+                Some((id.clone(), position))
+            })
+            .collect()
+    }
+
+
+    /// The (variant, id) of each node whose name contains query, sorted, e.g. for a search box.
+    /// At most limit results are returned if a limit is given 
+    pub fn search_nodes(&self, query: &str, case_insensitive: bool, limit: Option<usize>) -> Vec<(String, String)> {
+        let query = if case_insensitive { query.to_lowercase() } else { query.to_string() };
+        let mut found: Vec<(String, String)> = self.nodes.iter()
+            .flat_map(|(variant, collection)| collection.iter().map(move |(id, node)| (variant, id, node)))
+            .filter(|(_variant, _id, node)| match node["name"].as_str() {
+                Some(name) if case_insensitive => name.to_lowercase().contains(&query),
+                Some(name) => name.contains(&query),
+                None => false,
+            })
+            .map(|(variant, id, _node)| (variant.clone(), id.clone()))
+            .collect();
+        found.sort();
+        if let Some(limit) = limit {
+            found.truncate(limit);
+        }
+        found
+    }
+
+
+    /// The degree of every node: the number of edges (of any variant, in either direction) it is an endpoint of.
+    /// A self-loop counts twice, and nodes without edges have degree 0 
+    pub fn degrees(&self) -> HashMap<String, usize> {
+        let mut degrees: HashMap<String, usize> = self.nodes.values()
+            .flat_map(|collection| collection.keys().map(|id| (id.clone(), 0)))
+            .collect();
+        for edge in self.edges.values().flat_map(|collection| collection.values()) {
+            let (source, target) = edge_ends(edge);
+            for end in [source, target] {
+                if let Some(degree) = degrees.get_mut(end) {
+                    *degree += 1;
+                }
+            }
+        }
+        degrees
+    }
+
+
+    /// How many edges (of any variant) have id as their source 
+    pub fn out_degree(&self, id: &str) -> usize {
+        self.edges.values()
+            .flat_map(|collection| collection.values())
+            .filter(|edge| edge_ends(edge).0 == id)
+            .count()
+    }
+
+
+    /// How many edges (of any variant) have id as their target 
+    pub fn in_degree(&self, id: &str) -> usize {
+        self.edges.values()
+            .flat_map(|collection| collection.values())
+            .filter(|edge| edge_ends(edge).1 == id)
+            .count()
+    }
+
+
+    /// How many nodes the variant has, e.g. to work out the number of pages for node_page 
+    pub fn node_count(&self, variant: &str) -> usize {
+        self.nodes.get(variant).map_or(0, HashMap::len)
+    }
+
+
+    /// One page of a variant's nodes for lazy UIs: the nodes at positions offset..offset + limit when sorted by id,
+    /// as (id, node JSON). Sorting on every call keeps pages stable however the map is laid out, at O(n log n) per page.
+    /// Empty once offset runs past the end 
+    pub fn node_page(&self, variant: &str, offset: usize, limit: usize) -> Vec<(String, &serde_json::Value)> {
+        let Some(collection) = self.nodes.get(variant) else {
+            return Vec::new();
+        };
+        let mut entries: Vec<(&String, &serde_json::Value)> = collection.iter().collect();
+        entries.sort_by_key(|(id, _node)| *id);
+        entries.into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|(id, node)| (id.clone(), node))
+            .collect()
+    }
+
+
+    /// Deserialize just the props of the node id within variant, without reconstructing the whole Node.
+    /// Ok(None) if there is no such node 
+    pub fn node_props_as<T: DeserializeOwned>(&self, variant: &str, id: &str) -> Result<Option<T>, serde_json::Error> {
+        match self.nodes.get(variant).and_then(|collection| collection.get(id)) {
+            Some(node) => Ok(Some(T::deserialize(&node["props"])?)),
+            None => Ok(None),
+        }
+    }
+
+
+    /// The display name stored on the node id within variant, or None if there is no such node or its name is not a string 
+    pub fn node_name(&self, variant: &str, id: &str) -> Option<&str> {
+        self.nodes.get(variant)?.get(id)?["name"].as_str()
+    }
+
+
+    /// The display name stored on the node id, whatever its variant. See node_name 
+    pub fn node_name_by_id(&self, id: &str) -> Option<&str> {
+        self.get_node_by_id(id)?["name"].as_str()
+    }
+
+
+    /// Every edge with node_id as its source or target, as (variant, edge id, edge JSON), sorted by variant then id 
+    pub fn incident_edges(&self, node_id: &str) -> Vec<(&str, &str, &serde_json::Value)> {
+        let mut incident: Vec<(&str, &str, &serde_json::Value)> = self.edges.iter()
+            .flat_map(|(variant, collection)| collection.iter().map(move |(id, edge)| (variant.as_str(), id.as_str(), edge)))
+            .filter(|(_variant, _id, edge)| {
+                let (source, target) = edge_ends(edge);
+                source == node_id || target == node_id
+            })
+            .collect();
+        incident.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        incident
+    }
+
+
+    /// Set every edge's bundle to its variant, so the frontend bundles edges of the same type together 
+    pub fn auto_bundle_by_variant(&mut self) {
+        self.invalidate_cache();
+        for (variant, collection) in self.edges.iter_mut() {
+            for edge in collection.values_mut() {
+                if let Some(edge) = edge.as_object_mut() {
+                    edge.insert("bundle".to_string(), variant.as_str().into());
+                }
+            }
+        }
+    }
+
+
+    /// Every node as (variant, id), ordered by key_fn(node JSON), e.g. for a leaderboard of
+    /// `|node| node["props"]["score"].as_f64().unwrap_or(f64::NAN)`. A NaN key marks a missing or non-numeric prop
+    /// and sorts to the end whether descending or not. Equal keys are ordered by variant then id 
+    pub fn nodes_sorted_by<F: Fn(&serde_json::Value) -> f64>(&self, key_fn: F, descending: bool) -> Vec<(String, String)> {
+        let mut keyed: Vec<(f64, &str, &str)> = self.nodes.iter()
+            .flat_map(|(variant, collection)| collection.iter().map(|(id, node)| (key_fn(node), variant.as_str(), id.as_str())))
+            .collect();
+        keyed.sort_by(|a, b| {
+            let by_key = match (a.0.is_nan(), b.0.is_nan()) {
+                (false, false) if descending => b.0.total_cmp(&a.0),
+                (false, false) => a.0.total_cmp(&b.0),
+                (nan_a, nan_b) => nan_a.cmp(&nan_b),
+            };
+            by_key.then((a.1, a.2).cmp(&(b.1, b.2)))
+        });
+        keyed.into_iter().map(|(_key, variant, id)| (variant.to_string(), id.to_string())).collect()
+    }
+
+
+    /// Mark the edge id of variant as directed (drawn with an arrow) or not, e.g. for a graph mixing both kinds.
+    /// An edge whose JSON is not an object, e.g. from a hand-written document, is left as it is 
+    pub fn set_edge_directed(&mut self, variant: &str, id: &str, directed: bool) -> Result<(), GraphError> {
+        let edge = self.edges.get_mut(variant)
+            .and_then(|collection| collection.get_mut(id))
+            .ok_or_else(|| GraphError::EdgeNotFound(id.to_string()))?;
+        if let Some(edge) = edge.as_object_mut() {
+            edge.insert("directed".to_string(), directed.into());
+        }
+        self.touch_edge(variant, id);
+        Ok(())
+    }
+
+
+    /// Mark every edge as directed or not, skipping edges whose JSON is not an object 
+    pub fn set_all_edges_directed(&mut self, directed: bool) {
+        self.invalidate_cache();
+        for edge in self.edges.values_mut().flat_map(|collection| collection.values_mut()) {
+            if let Some(edge) = edge.as_object_mut() {
+                edge.insert("directed".to_string(), directed.into());
+            }
+        }
+    }
+
+
+    /// The (source, target) node JSON of the edge edge_id of edge_variant, e.g. for an edge detail panel.
+    /// None if there is no such edge or either end is missing from the graph 
+    pub fn edge_endpoints(&self, edge_variant: &str, edge_id: &str) -> Option<(&serde_json::Value, &serde_json::Value)> {
+        let edge = self.edges.get(edge_variant)?.get(edge_id)?;
+        let (source, target) = edge_ends(edge);
+        Some((self.get_node_by_id(source)?, self.get_node_by_id(target)?))
+    }
+
+
+    /// How many edges of each variant touch node_id, e.g. for a "5 cites, 2 wrote" summary. A self-loop counts once 
+    pub fn incident_edge_variants(&self, node_id: &str) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (variant, _id, _edge) in self.incident_edges(node_id) {
+            *counts.entry(variant.to_string()).or_default() += 1;
+        }
+        counts
+    }
+
+
+    /// true if both graphs hold the same nodes, edges and hyperedges, whatever order they were inserted in.
+    /// A variant with an empty collection counts the same as an absent one. Meta, layout_seed and generated_at are not compared 
+    pub fn semantically_eq(&self, other: &Graph) -> bool {
+        fn same(a: &HashMap<String, HashMap<String, serde_json::Value>>, b: &HashMap<String, HashMap<String, serde_json::Value>>) -> bool {
+            let non_empty = |collections: &HashMap<String, HashMap<String, serde_json::Value>>| collections.values().filter(|c| !c.is_empty()).count();
+            non_empty(a) == non_empty(b)
+                && a.iter().filter(|(_variant, c)| !c.is_empty()).all(|(variant, c)| b.get(variant) == Some(c))
+        }
+        same(&self.nodes, &other.nodes) && same(&self.edges, &other.edges) && same(&self.hyperedges, &other.hyperedges)
+    }
+
+
+    /// A coarse overview for level-of-detail rendering: every group of nodes sharing the same key_fn(node JSON) becomes
+    /// one "Group" node whose id and name are the key and whose props hold {"count": group size}. Edges between two
+    /// groups are aggregated into one "aggregate" edge per direction with props {"weight": number of underlying edges};
+    /// edges within a group and dangling edges are left out 
+    pub fn collapse_by<F: Fn(&serde_json::Value) -> String>(&self, key_fn: F) -> Graph {
+        let mut group_of: HashMap<&str, String> = HashMap::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for (id, node) in self.nodes.values().flat_map(|collection| collection.iter()) {
+            let key = key_fn(node);
+            *counts.entry(key.clone()).or_default() += 1;
+            group_of.insert(id, key);
+        }
+        let mut weights: HashMap<(&str, &str), usize> = HashMap::new();
+        for edge in self.edges.values().flat_map(|collection| collection.values()) {
+            let (source, target) = edge_ends(edge);
+            if let (Some(source), Some(target)) = (group_of.get(source), group_of.get(target)) {
+                if source != target {
+                    *weights.entry((source, target)).or_default() += 1;
+                }
+            }
+        }
+        let mut coarse = Graph::new();
+        for (key, count) in &counts {
+            let node = Node::new("Group", key, key.clone(), key.clone(), serde_json::json!({"count": count}));
+            coarse.add_node(&node).expect("a Node of strings and JSON always serializes");
+        }
+        for ((source, target), weight) in weights {
+            let edge = Edge{
+                variant: "aggregate",
+                variant_pk: (source, target),
+                id: format!("{}|aggregate|{}", source, target),
+                source: source.to_string(),
+                target: target.to_string(),
+                props: serde_json::json!({"weight": weight}),
+                valid_from: None,
+                valid_to: None,
+                label: Some("aggregate".to_string()),
+                bundle: None,
+                directed: None,
+            };
+            coarse.add_edge(&edge).expect("an Edge of strings and JSON always serializes");
+        }
+        coarse
+    }
+
+
+    /// give subgraph, already holding the nodes and edges taken from self, the graph-level state that goes with them:
+    /// meta (with zoom_id only if that node was kept), layout_seed, and the hyperedges all of whose members were kept 
+    fn carry_graph_state(&self, subgraph: &mut Graph) {
+        subgraph.meta = self.meta.clone();
+        if subgraph.meta.zoom_id.as_deref().is_some_and(|zoom_id| !subgraph.contains_node(zoom_id)) {
+            subgraph.meta.zoom_id = None;
+        }
+        subgraph.layout_seed = self.layout_seed;
+        subgraph.hyperedges = self.hyperedges.iter()
+            .map(|(variant, collection)| {
+                let kept = collection.iter()
+                    .filter(|(_id, hyperedge)| hyperedge["members"].as_array().is_some_and(|members| {
+                        members.iter().all(|member| member.as_str().is_some_and(|member| subgraph.contains_node(member)))
+                    }))
+                    .map(|(id, hyperedge)| (id.clone(), hyperedge.clone()))
+                    .collect::<HashMap<String, serde_json::Value>>();
+                (variant.clone(), kept)
+            })
+            .filter(|(_variant, kept)| !kept.is_empty())
+            .collect();
+    }
+
+
+    /// The subgraph induced by ids: the nodes whose id is in the set and the edges with both endpoints in it,
+    /// along with meta, layout_seed and the hyperedges whose members are all in it.
+    /// Ids not in the graph are ignored 
+    pub fn induced_subgraph(&self, ids: &HashSet<String>) -> Graph {
+        let mut subgraph = Graph::new();
+        for (variant, collection) in &self.nodes {
+            for (id, node) in collection.iter().filter(|(id, _node)| ids.contains(*id)) {
+                subgraph.nodes.entry(variant.clone()).or_default().insert(id.clone(), node.clone());
+            }
+        }
+        for (variant, collection) in &self.edges {
+            for (id, edge) in collection {
+                let (source, target) = edge_ends(edge);
+                if subgraph.contains_node(source) && subgraph.contains_node(target) {
+                    subgraph.edges.entry(variant.clone()).or_default().insert(id.clone(), edge.clone());
+                }
+            }
+        }
+        self.carry_graph_state(&mut subgraph);
+        subgraph
+    }
+
+
+    /// The connections between two groups: the nodes in a or b, and only the edges running from one group to the other
+    /// (in either direction), so edges within a group are left out. Meta, layout_seed and the hyperedges whose members
+    /// are all kept carry over, as in induced_subgraph. Ids not in the graph are ignored 
+    pub fn between_sets(&self, a: &HashSet<String>, b: &HashSet<String>) -> Graph {
+        let mut subgraph = Graph::new();
+        for (variant, collection) in &self.nodes {
+            for (id, node) in collection.iter().filter(|(id, _node)| a.contains(*id) || b.contains(*id)) {
+                subgraph.nodes.entry(variant.clone()).or_default().insert(id.clone(), node.clone());
+            }
+        }
+        for (variant, collection) in &self.edges {
+            for (id, edge) in collection {
+                let (source, target) = edge_ends(edge);
+                let crosses = (a.contains(source) && b.contains(target)) || (b.contains(source) && a.contains(target));
+                if crosses && subgraph.contains_node(source) && subgraph.contains_node(target) {
+                    subgraph.edges.entry(variant.clone()).or_default().insert(id.clone(), edge.clone());
+                }
+            }
+        }
+        self.carry_graph_state(&mut subgraph);
+        subgraph
+    }
+}
+
+
+
+
+/// A view into a single node slot of a Graph, obtained from Graph::node_entry. Loosely modelled on std's hash_map::Entry 
+pub struct NodeEntry<'a> {
+    entry: Entry<'a, String, serde_json::Value>,
+    /// the graph's id index, if enabled, and the variant to record there if a node is inserted 
+    index: Option<(&'a mut HashMap<String, String>, String)>,
+}
+
+impl<'a> NodeEntry<'a> {
+    /// the id of the node this entry is for 
+    pub fn id(&self) -> &str {
+        self.entry.key()
+    }
+
+    /// true if the node is already in the graph 
+    pub fn is_occupied(&self) -> bool {
+        matches!(self.entry, Entry::Occupied(_))
+    }
+
+    /// return the existing node, or insert the result of f if there is none 
+    pub fn or_insert_with<F: FnOnce() -> serde_json::Value>(self, f: F) -> &'a mut serde_json::Value {
+        let NodeEntry{entry, index} = self;
+        if let (Entry::Vacant(vacant), Some((index, variant))) = (&entry, index) {
+            index.insert(vacant.key().clone(), variant);
+        }
+        entry.or_insert_with(f)
+    }
+
+    /// return the existing node, or insert node if there is none 
+    pub fn or_insert(self, node: serde_json::Value) -> &'a mut serde_json::Value {
+        self.or_insert_with(|| node)
+    }
+}
+
+
+
+
+/// this trait simply means you define an implementation of to_graph, which must return a Graph struct 
+pub trait ToGraph {
+    /// Define how an existing graph should have content added to it from this struct 
+    fn mut_graph(&self, graph: &mut Graph) -> Result<(), serde_json::Error>;
+    /// The id of the node the frontend should zoom to, stamped into the meta of graphs built by to_graph.
+    /// This covers the common case without implementing ZoomNode as well. When it is None, any zoom_id
+    /// set by mut_graph is kept 
+    fn default_zoom_id(&self) -> Option<String> {
+        None
+    }
+    fn to_graph(&self) -> Result<Graph, serde_json::Error> {
+        let mut g = Graph::new();
+        self.mut_graph(&mut g)?;
+        if let Some(zoom_id) = self.default_zoom_id() {
+            g.meta.zoom_id = Some(zoom_id);
+        }
+        Ok(g)
+    }
+}
+
+
+/// If a struct implements ToGraph, you may wish for it to also implement FocusNode.  
+/// The idea here is that if you display a (sub)graph for the user to see,
+/// you may wish to zoom to a particular node as the 'starting point'
+/// or other node of interest. 
+/// The ZoomNode trait allows that functionality by providing a variant and variant_pk,
+/// much like the ToNode trait 
+pub trait ZoomNode<EV: fmt::Display, PK: fmt::Debug>: ToGraph {
+    /// EV is the variant, PK is the variant_pk
+    fn zoom_to(&self) -> Option<(EV, PK)>;
+    /// the graph3d.js :: zoomToId() function expects a node id = 'variant|PK'
+    fn zoom_to_id(&self) -> Option<String> {
+        self.zoom_to().map(|(variant, pk)| format!("{}|{:?}", variant, pk))
+    }
+}
+
+
+/// Graph::source_edge_target works for any pair of nodes, but its eight generics make it verbose at the call site.
+/// For a fixed schema relationship, e.g. "an Author wrote an Article", implement ConnectsTo on the source type
+/// to declare the edge variant and props once. Callers can then simply write `author.connect_to(&article, &mut graph)` 
+pub trait ConnectsTo<Target, NVS, PKS, TS, NVT, PKT, TT>: ToNode<NVS, PKS, TS> where 
+    Target: ToNode<NVT, PKT, TT>,
+    NVS: Serialize + fmt::Display,
+    PKS: Serialize + fmt::Debug,
+    TS:  Serialize,
+    NVT: Serialize + fmt::Display,
+    PKT: Serialize + fmt::Debug,
+    TT:  Serialize,
+{
+    /// The type of the edge variant, typically a String or an enum that implements std::fmt::Display 
+    type EdgeVariant: Serialize + fmt::Display;
+    /// The type of the edge props 
+    type EdgeProps: Serialize;
+    /// The variant of the edge from self to target 
+    fn connection_variant(&self, target: &Target) -> Self::EdgeVariant;
+    /// The props of the edge from self to target 
+    fn connection_props(&self, target: &Target) -> Self::EdgeProps;
+    /// Add self, target and the edge between them to graph, returning the edge 
+    #[allow(clippy::type_complexity)]
+    fn connect_to(&self, target: &Target, graph: &mut Graph) -> Result<Edge<Self::EdgeVariant, (PKS, PKT), Self::EdgeProps>, serde_json::Error> where 
+        Self: Sized
+    {
+        let (_source, edge, _target) = graph.source_edge_target(self, target, self.connection_variant(target), self.connection_props(target))?;
+        Ok(edge)
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::*;
+
+    #[test]
+    fn add_node_from_groups_by_variant() {
+        let mut graph = Graph::new();
+        graph.add_node_from(&thing(1)).unwrap();
+        graph.add_node_from(&thing_of("Other", 1)).unwrap();
+        assert_eq!(graph.nodes["Thing"]["Thing|1"]["name"], "Thing 1");
+        assert!(graph.nodes["Other"].contains_key("Other|1"));
+    }
+
+    #[test]
+    fn props_builder_produces_object() {
+        let props = EdgeProps::new().set("weight", 3).set("comment", "cites").set("weight", 4);
+        assert_eq!(props.clone().to_value(), serde_json::json!({"weight": 4, "comment": "cites"}));
+        let mut graph = Graph::new();
+        let (_, edge, _) = graph.source_edge_target(&thing(1), &thing(2), "link", props).unwrap();
+        assert_eq!(edge.to_edge_json().unwrap()["props"], serde_json::json!({"weight": 4, "comment": "cites"}));
+        assert_eq!(serde_json::Value::from(NodeProps::new().set("x", true)), serde_json::json!({"x": true}));
+    }
+
+    #[test]
+    fn contract_node_reroutes_edges() {
+        // 1 -> 2 -> 3, contracting 2 into 1 leaves 1 -> 3 
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.contract_node(&id(2), &id(1)).unwrap();
+        assert!(!graph.contains_node(&id(2)));
+        let links = &graph.edges["link"];
+        assert_eq!(links.len(), 1);
+        let edge = &links[&edge_id(2, 3)];
+        assert_eq!(edge["source"], id(1));
+        assert_eq!(edge["target"], id(3));
+        assert!(matches!(graph.contract_node(&id(9), &id(1)), Err(GraphError::NodeNotFound(missing)) if missing == id(9)));
+    }
+
+    #[test]
+    fn parallel_edges_need_an_edge_pk() {
+        let mut graph = Graph::new();
+        graph.source_edge_target(&thing(1), &thing(2), "cites", ()).unwrap();
+        graph.source_edge_target(&thing(1), &thing(2), "cites", ()).unwrap();
+        assert_eq!(graph.edges["cites"].len(), 1);
+        graph.source_edge_target_with_pk(&thing(1), &thing(2), "cites", 1, ()).unwrap();
+        graph.source_edge_target_with_pk(&thing(1), &thing(2), "cites", 2, ()).unwrap();
+        assert_eq!(graph.edges["cites"].len(), 3);
+        assert!(graph.edges["cites"].contains_key("1|cites|2|1"));
+        assert!(graph.edges["cites"].contains_key("1|cites|2|2"));
+    }
+
+    #[test]
+    fn edge_variant_endpoints_lists_variant_pairs() {
+        let mut graph = Graph::new();
+        graph.source_edge_target(&thing_of("Author", 1), &thing_of("Article", 1), "wrote", ()).unwrap();
+        graph.source_edge_target(&thing_of("Author", 2), &thing_of("Article", 1), "wrote", ()).unwrap();
+        graph.source_edge_target(&thing_of("Author", 2), &thing_of("Book", 1), "wrote", ()).unwrap();
+        let pairs = graph.edge_variant_endpoints("wrote");
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs.contains(&("Author".to_string(), "Article".to_string())));
+        assert!(pairs.contains(&("Author".to_string(), "Book".to_string())));
+        assert!(graph.edge_variant_endpoints("cites").is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn from_stream_adds_every_item() {
+        let items = futures::stream::iter(vec![thing(1), thing(2), thing_of("Other", 1)]);
+        let graph = futures::executor::block_on(Graph::from_stream(items)).unwrap();
+        assert_eq!(graph.nodes["Thing"].len(), 2);
+        assert!(graph.nodes["Thing"].contains_key("Thing|2"));
+        assert!(graph.nodes["Other"].contains_key("Other|1"));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn from_stream_with_progress_reports_counts() {
+        let items = futures::stream::iter((1..=5).map(thing));
+        let mut counts = Vec::new();
+        let graph = futures::executor::block_on(Graph::from_stream_with_progress(items, 2, |count| counts.push(count))).unwrap();
+        assert_eq!(graph.nodes["Thing"].len(), 5);
+        assert_eq!(counts, vec![2, 4]);
+    }
+
+    #[test]
+    fn from_nodes_with_progress_reports_counts() {
+        let mut counts = Vec::new();
+        let graph = Graph::from_nodes_with_progress((1..=10).map(thing), 3, |count| counts.push(count)).unwrap();
+        assert_eq!(graph.nodes["Thing"].len(), 10);
+        assert_eq!(counts, vec![3, 6, 9]);
+        assert_eq!(Graph::from_nodes((1..=10).map(thing)).unwrap().nodes["Thing"].len(), 10);
+    }
+
+    #[test]
+    fn emptied_variants_are_dropped() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.add_node_from(&thing_of("Other", 1)).unwrap();
+        graph.add_node_from(&thing_of("Other", 2)).unwrap();
+        graph.nodes.get_mut("Other").unwrap().remove("Other|2");
+        graph.remove_node("Other|1");
+        assert!(!graph.nodes.contains_key("Other"));
+        graph.remove_node(&id(1));
+        assert!(!graph.edges.contains_key("link"));
+        graph.nodes.insert("Empty".to_string(), HashMap::with_capacity(64));
+        graph.hyperedges.insert("coauthored".to_string(), HashMap::with_capacity(64));
+        graph.hyperedges.get_mut("coauthored").unwrap().insert("coauthored|1".to_string(), serde_json::json!({"members": []}));
+        graph.shrink_to_fit();
+        assert!(!graph.nodes.contains_key("Empty"));
+        assert_eq!(graph.nodes["Thing"].len(), 1);
+        assert!(graph.hyperedges["coauthored"].capacity() < 64);
+    }
+
+    struct Author {
+        name: String,
+    }
+
+    impl ToNode<String, String, ()> for Author {
+        fn node_variant(&self) -> String {
+            "Author".to_string()
+        }
+        fn node_pk(&self) -> String {
+            self.name.clone()
+        }
+        fn node_name(&self) -> String {
+            self.name.clone()
+        }
+        fn node_props(&self) {}
+    }
+
+    impl ConnectsTo<Thing, String, String, (), String, i32, ()> for Author {
+        type EdgeVariant = &'static str;
+        type EdgeProps = Props;
+        fn connection_variant(&self, _target: &Thing) -> &'static str {
+            "wrote"
+        }
+        fn connection_props(&self, target: &Thing) -> Props {
+            Props::new().set("title", target.name.clone())
+        }
+    }
+
+    #[test]
+    fn connect_to_adds_nodes_and_edge() {
+        let mut graph = Graph::new();
+        let author = Author{name: "Ann".to_string()};
+        let edge = author.connect_to(&thing(1), &mut graph).unwrap();
+        assert_eq!(edge.source, "Author|\"Ann\"");
+        assert_eq!(edge.target, id(1));
+        assert!(graph.contains_node("Author|\"Ann\""));
+        assert!(graph.contains_node(&id(1)));
+        assert_eq!(graph.edges["wrote"][&edge.id]["props"]["title"], "Thing 1");
+    }
+
+    struct Article {
+        pk: i32,
+        props: Props,
+    }
+
+    impl ToNode<String, i32, Props> for Article {
+        fn node_variant(&self) -> String {
+            "Article".to_string()
+        }
+        fn node_pk(&self) -> i32 {
+            self.pk
+        }
+        fn node_name(&self) -> String {
+            format!("article {}", self.pk)
+        }
+        fn node_props(&self) -> Props {
+            self.props.clone()
+        }
+    }
+
+    impl ToNodeJSON<String, i32, Props> for Article {}
+
+    impl ToNodeRef<String, i32, Props> for Article {
+        fn node_props_ref(&self) -> &Props {
+            &self.props
+        }
+    }
+
+    #[test]
+    fn borrowed_props_serialize_like_owned() {
+        let article = Article{pk: 3, props: Props::new().set("abstract", "a long abstract").set("pages", 12)};
+        let borrowed = article.to_node_json_borrowed().unwrap();
+        assert_eq!(borrowed, ToNodeJSON::to_node_json(&article).unwrap());
+        assert_eq!(borrowed["props"]["pages"], 12);
+        let mut graph = Graph::new();
+        graph.add_node(&article.to_node_borrowed()).unwrap();
+        assert_eq!(graph.nodes["Article"]["Article|3"], borrowed);
+    }
+
+    #[test]
+    fn union_all_combines_overlapping_graphs() {
+        let mut last = graph_from_edges(&[(3, 4)]);
+        last.nodes.get_mut("Thing").unwrap().get_mut(&id(3)).unwrap()["name"] = "last".into();
+        let graphs = vec![graph_from_edges(&[(1, 2)]), graph_from_edges(&[(2, 3)]), last];
+        let union = Graph::union_all(graphs);
+        let ids: HashSet<&String> = union.nodes["Thing"].keys().collect();
+        assert_eq!(ids, [id(1), id(2), id(3), id(4)].iter().collect());
+        assert_eq!(union.edges["link"].len(), 3);
+        assert_eq!(union.nodes["Thing"][&id(3)]["name"], "last");
+    }
+
+    #[test]
+    fn edges_between_honors_direction() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.source_edge_target(&thing(1), &thing(2), "cites", ()).unwrap();
+        assert_eq!(graph.edges_between(&id(1), &id(2), true), vec!["1|cites|2".to_string(), edge_id(1, 2)]);
+        assert!(graph.edges_between(&id(2), &id(1), true).is_empty());
+        assert_eq!(graph.edges_between(&id(2), &id(1), false).len(), 2);
+        assert!(graph.edges_between(&id(1), &id(3), false).is_empty());
+    }
+
+    struct Employment {
+        from: i32,
+        to: i32,
+        window: (Option<i64>, Option<i64>),
+    }
+
+    impl ToEdge<&'static str, (i32, i32), ()> for Employment {
+        fn edge_variant(&self) -> &'static str {
+            "employed"
+        }
+        fn edge_pk(&self) -> (i32, i32) {
+            (self.from, self.to)
+        }
+        fn edge_source(&self) -> String {
+            id(self.from)
+        }
+        fn edge_target(&self) -> String {
+            id(self.to)
+        }
+        fn edge_props(&self) {}
+        fn edge_validity(&self) -> (Option<i64>, Option<i64>) {
+            self.window
+        }
+    }
+
+    #[test]
+    fn at_time_keeps_only_active_edges() {
+        let mut graph = Graph::new();
+        for pk in 1..=4 {
+            graph.add_node_from(&thing(pk)).unwrap();
+        }
+        graph.add_edge(&Employment{from: 1, to: 2, window: (Some(100), Some(200))}.to_edge()).unwrap();
+        graph.add_edge(&Employment{from: 2, to: 3, window: (Some(200), None)}.to_edge()).unwrap();
+        graph.add_edge(&Employment{from: 3, to: 4, window: (None, None)}.to_edge()).unwrap();
+        let at_150 = graph.at_time(150, false);
+        assert_eq!(at_150.edges["employed"].len(), 2);
+        assert!(!at_150.edges["employed"].contains_key("employed|(2, 3)"));
+        assert!(at_150.edges["employed"].contains_key("employed|(1, 2)"));
+        let at_200 = graph.at_time(200, false);
+        assert!(!at_200.edges["employed"].contains_key("employed|(1, 2)"));
+        assert!(!at_200.contains_node(&id(1)));
+        assert!(graph.at_time(200, true).contains_node(&id(1)));
+
+        graph.add_hyperedge(&Coauthorship(vec![1, 2]).to_hyperedge()).unwrap();
+        assert!(graph.at_time(150, false).hyperedges["coauthored"].contains_key("coauthored|[1, 2]"));
+        assert!(graph.at_time(200, false).hyperedges.is_empty());
+        assert!(graph.at_time(200, true).hyperedges["coauthored"].contains_key("coauthored|[1, 2]"));
+    }
+
+    #[test]
+    fn node_entry_inserts_once() {
+        let mut graph = Graph::new();
+        let mut calls = 0;
+        for name in ["first", "second"] {
+            let entry = graph.node_entry("Thing", &id(1));
+            assert_eq!(entry.id(), id(1));
+            entry.or_insert_with(|| {
+                calls += 1;
+                serde_json::json!({"id": id(1), "name": name})
+            });
+        }
+        assert_eq!(calls, 1);
+        assert!(graph.node_entry("Thing", &id(1)).is_occupied());
+        assert_eq!(graph.nodes["Thing"][&id(1)]["name"], "first");
+    }
+
+    struct Review;
+
+    impl ToEdge<&'static str, i32, ()> for Review {
+        fn edge_variant(&self) -> &'static str {
+            "reviewed"
+        }
+        fn edge_pk(&self) -> i32 {
+            1
+        }
+        fn edge_source(&self) -> String {
+            id(1)
+        }
+        fn edge_target(&self) -> String {
+            id(2)
+        }
+        fn edge_props(&self) {}
+        fn edge_label(&self) -> Option<String> {
+            Some("peer reviewed".to_string())
+        }
+    }
+
+    #[test]
+    fn edge_label_defaults_to_variant() {
+        let employment = Employment{from: 1, to: 2, window: (None, None)}.to_edge();
+        assert_eq!(employment.label.as_deref(), Some("employed"));
+        assert_eq!(Review.to_edge().to_edge_json().unwrap()["label"], "peer reviewed");
+        let mut graph = Graph::new();
+        let (_, edge, _) = graph.source_edge_target(&thing(1), &thing(2), "cites", ()).unwrap();
+        assert_eq!(graph.edges["cites"][&edge.id]["label"], "cites");
+    }
+
+    #[test]
+    fn from_json_validated_rejects_dangling_edges() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        let json = serde_json::to_string(&graph).unwrap();
+        assert!(Graph::from_json_validated(&json).is_ok());
+        let mut broken = graph;
+        broken.nodes.get_mut("Thing").unwrap().remove(&id(3));
+        let json = serde_json::to_string(&broken).unwrap();
+        match Graph::from_json_validated(&json) {
+            Err(GraphError::DanglingEdges(ids)) => assert_eq!(ids, vec![edge_id(2, 3)]),
+            _ => panic!("expected dangling edges"),
+        }
+        assert!(matches!(Graph::from_json_validated("{"), Err(GraphError::Json(_))));
+    }
+
+    #[test]
+    fn cap_per_variant_trims_and_drops_dangling_edges() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4)]);
+        graph.source_edge_target(&thing_of("Other", 1), &thing(1), "link", ()).unwrap();
+        graph.cap_per_variant(2);
+        let ids: HashSet<&String> = graph.nodes["Thing"].keys().collect();
+        assert_eq!(ids, [id(1), id(2)].iter().collect());
+        assert!(graph.contains_node("Other|1"));
+        assert_eq!(graph.edges["link"].len(), 2);
+        assert!(graph.validate().is_ok());
+    }
+
+    struct Described;
+
+    impl ToNode<&'static str, i32, ()> for Described {
+        fn node_variant(&self) -> &'static str {
+            "Described"
+        }
+        fn node_pk(&self) -> i32 {
+            1
+        }
+        fn node_name(&self) -> String {
+            "short".to_string()
+        }
+        fn node_props(&self) {}
+        fn node_desc(&self) -> Option<String> {
+            Some("a much longer description".to_string())
+        }
+        fn node_badges(&self) -> Vec<String> {
+            vec!["verified".to_string(), "https://example.org/flag.svg".to_string()]
+        }
+        fn node_has_more(&self) -> Option<bool> {
+            Some(true)
+        }
+        fn node_shape(&self) -> Option<String> {
+            Some("cube".to_string())
+        }
+    }
+
+    #[test]
+    fn node_desc_is_serialized_only_when_present() {
+        let json = Described.to_node().to_node_json().unwrap();
+        assert_eq!(json["desc"], "a much longer description");
+        assert_eq!(json["name"], "short");
+        let json = thing(1).to_node().to_node_json().unwrap();
+        assert!(json.get("desc").is_none());
+    }
+
+    #[test]
+    fn map_ids_keeps_edges_consistent() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        let mut calls = 0;
+        graph.map_ids(|id| {
+            calls += 1;
+            format!("anon:{}", id.replace("Thing", "T"))
+        });
+        // 3 node ids and 2 edge ids 
+        assert_eq!(calls, 5);
+        assert!(!graph.contains_node(&id(1)));
+        assert_eq!(graph.nodes["Thing"]["anon:T|1"]["id"], "anon:T|1");
+        let edge = &graph.edges["link"][&format!("anon:{}", edge_id(1, 2))];
+        assert_eq!(edge["id"], format!("anon:{}", edge_id(1, 2)));
+        assert_eq!(edge["source"], "anon:T|1");
+        assert_eq!(edge["target"], "anon:T|2");
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn map_ids_rekeys_non_object_values() {
+        let mut graph: Graph = serde_json::from_str(r#"{"nodes": {"odd": {"x": 5}}, "edges": {"odd": {"y": [1]}}}"#).unwrap();
+        graph.map_ids(|id| format!("anon:{}", id));
+        assert_eq!(graph.nodes["odd"]["anon:x"], 5);
+        assert_eq!(graph.edges["odd"]["anon:y"], serde_json::json!([1]));
+    }
+
+    struct Pair(i32, i32);
+
+    impl ToGraph for Pair {
+        fn mut_graph(&self, graph: &mut Graph) -> Result<(), serde_json::Error> {
+            graph.source_edge_target(&thing(self.0), &thing(self.1), "link", ())?;
+            Ok(())
+        }
+        fn default_zoom_id(&self) -> Option<String> {
+            Some(id(self.0))
+        }
+    }
+
+    #[test]
+    fn to_graph_stamps_zoom_id() {
+        let graph = Pair(1, 2).to_graph().unwrap();
+        assert_eq!(graph.meta.zoom_id, Some(id(1)));
+        assert_eq!(serde_json::to_value(&graph).unwrap()["meta"]["zoom_id"], id(1));
+        assert!(serde_json::to_value(Graph::new()).unwrap()["meta"].get("zoom_id").is_none());
+    }
+
+    struct ZoomsItself;
+
+    impl ToGraph for ZoomsItself {
+        fn mut_graph(&self, graph: &mut Graph) -> Result<(), serde_json::Error> {
+            graph.add_node_from(&thing(1))?;
+            graph.meta.zoom_id = Some(id(1));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn to_graph_keeps_a_zoom_id_set_by_mut_graph() {
+        assert_eq!(ZoomsItself.to_graph().unwrap().meta.zoom_id, Some(id(1)));
+    }
+
+    #[test]
+    fn neighbor_map_matches_neighbors() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 4)]);
+        let ids = [id(1), id(3), id(4), id(9)];
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let map = graph.neighbor_map(&ids);
+        assert_eq!(map.len(), 4);
+        for id in ids {
+            assert_eq!(map[id], graph.neighbors(id));
+        }
+    }
+
+    #[test]
+    fn layout_seed_serializes_only_when_set() {
+        let mut graph = Graph::new();
+        assert!(serde_json::to_value(&graph).unwrap().get("layout_seed").is_none());
+        graph.set_layout_seed(42);
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["layout_seed"], 42);
+        let back: Graph = serde_json::from_value(json).unwrap();
+        assert_eq!(back.layout_seed, Some(42));
+    }
+
+    #[test]
+    fn index_lookup_agrees_with_scan() {
+        let mut indexed = Graph::with_index();
+        let mut plain = Graph::new();
+        for graph in [&mut indexed, &mut plain] {
+            link(graph, 1, 2);
+            link(graph, 2, 3);
+            graph.add_node_from(&thing_of("Other", 1)).unwrap();
+            graph.node_entry("Other", "Other|2").or_insert(serde_json::json!({"id": "Other|2"}));
+            graph.remove_node(&id(3));
+            graph.map_ids(|id| format!("x{}", id));
+        }
+        for id in ["xThing|1", "xThing|2", "xThing|3", "xOther|1", "Other|2", "xOther|2", "nope"] {
+            assert_eq!(indexed.get_node_by_id(id), plain.get_node_by_id(id));
+        }
+        assert!(indexed.get_node_by_id("xOther|2").is_some());
+        assert_eq!(indexed.index.as_ref().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn reverse_edges_swaps_in_and_out() {
+        let mut graph = graph_from_edges(&[(1, 2), (1, 3), (4, 1)]);
+        assert_eq!(graph.out_neighbors(&id(1)), vec![id(2), id(3)]);
+        assert_eq!(graph.in_neighbors(&id(1)), vec![id(4)]);
+        graph.reverse_edges();
+        assert_eq!(graph.out_neighbors(&id(1)), vec![id(4)]);
+        assert_eq!(graph.in_neighbors(&id(1)), vec![id(2), id(3)]);
+        let edge = &graph.edges["link"][&edge_id(1, 2)];
+        assert_eq!(edge["source"], id(2));
+        assert_eq!(edge["variant_pk"], serde_json::json!([2, 1]));
+        graph.edges.entry("odd".to_string()).or_default().insert("x".to_string(), 5.into());
+        graph.reverse_edges();
+        assert_eq!(graph.edges["odd"]["x"], 5);
+    }
+
+    #[test]
+    fn apply_positions_pins_matching_nodes() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        let positions = HashMap::from([(id(1), (1.0, 2.0, 3.0)), (id(9), (0.0, 0.0, 0.0))]);
+        graph.apply_positions(&positions);
+        let node = &graph.nodes["Thing"][&id(1)];
+        assert_eq!((node["fx"].as_f64(), node["fy"].as_f64(), node["fz"].as_f64()), (Some(1.0), Some(2.0), Some(3.0)));
+        assert!(graph.nodes["Thing"][&id(2)].get("fx").is_none());
+        assert!(!graph.contains_node(&id(9)));
+        graph.nodes.get_mut("Thing").unwrap().insert(id(3), serde_json::json!([3]));
+        graph.apply_positions(&HashMap::from([(id(3), (1.0, 2.0, 3.0))]));
+        assert_eq!(graph.nodes["Thing"][&id(3)], serde_json::json!([3]));
+    }
+
+    #[test]
+    fn search_nodes_by_name() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 13)]);
+        graph.add_node_from(&thing_of("Other", 1)).unwrap();
+        assert_eq!(graph.search_nodes("thing 1", false, None), vec![]);
+        let found = graph.search_nodes("thing 1", true, None);
+        assert_eq!(found, vec![("Thing".to_string(), id(1)), ("Thing".to_string(), id(13))]);
+        assert_eq!(graph.search_nodes("Thing 1", false, Some(1)), vec![("Thing".to_string(), id(1))]);
+        assert_eq!(graph.search_nodes("er 1", false, None), vec![("Other".to_string(), "Other|1".to_string())]);
+    }
+
+    #[test]
+    fn dedupe_by_merges_nodes_and_repoints_edges() {
+        let mut graph = graph_from_edges(&[(1, 3), (2, 4), (1, 2)]);
+        for pk in [1, 2] {
+            graph.nodes.get_mut("Thing").unwrap().get_mut(&id(pk)).unwrap()["props"] = serde_json::json!({"email": "a@x.org"});
+        }
+        graph.dedupe_by(|node| node["props"]["email"].as_str().map_or_else(|| node["id"].to_string(), String::from));
+        assert!(graph.contains_node(&id(1)) && !graph.contains_node(&id(2)));
+        assert_eq!(graph.nodes["Thing"].len(), 3);
+        // the 1 -> 2 edge became a self-loop and was dropped 
+        assert_eq!(graph.edges["link"].len(), 2);
+        assert_eq!(graph.edges["link"][&edge_id(2, 4)]["source"], id(1));
+        assert_eq!(graph.neighbors(&id(1)), vec![id(3), id(4)]);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Rating {
+        stars: u8,
+    }
+
+    #[test]
+    fn node_props_as_deserializes_props() {
+        let mut graph = Graph::new();
+        let node = Node::new("Review", 1, "Review|1".to_string(), "review".to_string(), Props::new().set("stars", 4));
+        graph.add_node(&node).unwrap();
+        assert_eq!(graph.node_props_as::<Rating>("Review", "Review|1").unwrap(), Some(Rating{stars: 4}));
+        assert_eq!(graph.node_props_as::<Rating>("Review", "Review|2").unwrap(), None);
+        assert!(graph.node_props_as::<String>("Review", "Review|1").is_err());
+    }
+
+    #[test]
+    fn from_reader_parses_a_stream() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        let bytes = serde_json::to_vec(&graph).unwrap();
+        let back = Graph::from_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert_eq!(back.nodes, graph.nodes);
+        assert_eq!(back.edges, graph.edges);
+        assert!(Graph::from_reader(std::io::Cursor::new(b"[]")).is_err());
+    }
+
+    #[test]
+    fn filter_edges_by_weight() {
+        let mut graph = Graph::new();
+        for (a, b, weight) in [(1, 2, 0.5), (2, 3, 2.0), (3, 4, 3.5)] {
+            graph.source_edge_target(&thing(a), &thing(b), "link", Props::new().set("weight", weight)).unwrap();
+        }
+        graph.add_node_from(&thing(5)).unwrap();
+        let heavy = graph.filter_edges(|_variant, _id, edge| edge["props"]["weight"].as_f64().unwrap_or_default() > 1.0);
+        let ids: HashSet<&String> = heavy.edges["link"].keys().collect();
+        assert_eq!(ids, [edge_id(2, 3), edge_id(3, 4)].iter().collect());
+        assert_eq!(heavy.nodes["Thing"].len(), 3);
+        assert!(!heavy.contains_node(&id(1)) && !heavy.contains_node(&id(5)));
+
+        graph.add_hyperedge(&Coauthorship(vec![2, 3, 4]).to_hyperedge()).unwrap();
+        graph.add_hyperedge(&Coauthorship(vec![1, 2]).to_hyperedge()).unwrap();
+        let heavy = graph.filter_edges(|_variant, _id, edge| edge["props"]["weight"].as_f64().unwrap_or_default() > 1.0);
+        let hyperedges: Vec<&String> = heavy.hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|[2, 3, 4]"]);
+    }
+
+    #[test]
+    fn node_name_lookup() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        assert_eq!(graph.node_name("Thing", &id(1)), Some("Thing 1"));
+        assert_eq!(graph.node_name_by_id(&id(2)), Some("Thing 2"));
+        assert_eq!(graph.node_name("Other", &id(1)), None);
+        assert_eq!(graph.node_name_by_id(&id(3)), None);
+        graph.nodes.get_mut("Thing").unwrap().get_mut(&id(1)).unwrap()["name"] = 7.into();
+        assert_eq!(graph.node_name("Thing", &id(1)), None);
+    }
+
+    #[test]
+    fn incident_edges_has_in_and_out() {
+        let mut graph = graph_from_edges(&[(1, 2), (3, 1), (2, 3)]);
+        graph.source_edge_target(&thing(1), &thing(3), "cites", ()).unwrap();
+        let incident = graph.incident_edges(&id(1));
+        let keys: Vec<(&str, &str)> = incident.iter().map(|(variant, id, _edge)| (*variant, *id)).collect();
+        assert_eq!(keys, vec![("cites", "1|cites|3"), ("link", "1|link|2"), ("link", "3|link|1")]);
+        assert_eq!(incident[2].2["target"], id(1));
+        assert!(graph.incident_edges(&id(9)).is_empty());
+    }
+
+    struct Coauthorship(Vec<i32>);
+
+    impl ToHyperEdge<&'static str, Vec<i32>, ()> for Coauthorship {
+        fn hyperedge_variant(&self) -> &'static str {
+            "coauthored"
+        }
+        fn hyperedge_pk(&self) -> Vec<i32> {
+            self.0.clone()
+        }
+        fn hyperedge_members(&self) -> Vec<String> {
+            self.0.iter().map(|pk| id(*pk)).collect()
+        }
+        fn hyperedge_props(&self) {}
+    }
+
+    #[test]
+    fn hyperedges_require_every_member() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.add_hyperedge(&Coauthorship(vec![1, 2, 3]).to_hyperedge()).unwrap();
+        let stored = &graph.hyperedges["coauthored"]["coauthored|[1, 2, 3]"];
+        assert_eq!(stored["members"], serde_json::json!([id(1), id(2), id(3)]));
+        match graph.add_hyperedge(&Coauthorship(vec![1, 4]).to_hyperedge()) {
+            Err(GraphError::NodeNotFound(missing)) => assert_eq!(missing, id(4)),
+            _ => panic!("expected a missing member"),
+        }
+        assert_eq!(graph.hyperedges["coauthored"].len(), 1);
+        assert!(serde_json::to_value(Graph::new()).unwrap().get("hyperedges").is_none());
+    }
+
+    #[test]
+    fn hyperedge_members_follow_removed_and_merged_nodes() {
+        let members = |graph: &Graph| graph.hyperedges["coauthored"]["coauthored|[1, 2, 3]"]["members"].clone();
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (5, 6)]);
+        graph.add_hyperedge(&Coauthorship(vec![1, 2, 3]).to_hyperedge()).unwrap();
+        graph.remove_node(&id(2));
+        assert_eq!(members(&graph), serde_json::json!([id(1), id(3)]));
+        graph.contract_node(&id(3), &id(4)).unwrap();
+        assert_eq!(members(&graph), serde_json::json!([id(1), id(4)]));
+        graph.dedupe_by(|_node| "same".to_string());
+        assert_eq!(members(&graph), serde_json::json!([id(1)]));
+        assert!(graph.validate().is_ok());
+        graph.cap_per_variant(0);
+        assert!(graph.hyperedges.is_empty());
+
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (5, 6)]);
+        graph.add_hyperedge(&Coauthorship(vec![1, 2, 3]).to_hyperedge()).unwrap();
+        graph.add_hyperedge(&Coauthorship(vec![5, 6]).to_hyperedge()).unwrap();
+        graph.retain_largest_component();
+        assert_eq!(graph.hyperedges["coauthored"].len(), 1);
+        // only the hyperedge dangles, since validate reports dangling edges first 
+        graph.edges.clear();
+        assert!(graph.nodes.get_mut("Thing").unwrap().remove(&id(3)).is_some());
+        match graph.validate() {
+            Err(GraphError::DanglingHyperedges(ids)) => assert_eq!(ids, vec!["coauthored|[1, 2, 3]".to_string()]),
+            _ => panic!("expected a dangling hyperedge"),
+        }
+    }
+
+    #[test]
+    fn semantic_equality_ignores_insertion_order() {
+        let forward = graph_from_edges(&[(1, 2), (2, 3), (3, 4)]);
+        let mut backward = graph_from_edges(&[(3, 4), (2, 3), (1, 2)]);
+        backward.nodes.insert("Empty".to_string(), HashMap::new());
+        assert!(forward.semantically_eq(&backward));
+        backward.remove_node(&id(4));
+        assert!(!forward.semantically_eq(&backward));
+    }
+
+    #[test]
+    fn variant_styles_serialize_under_meta() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.set_variant_style("Thing", serde_json::json!({"color": "#ff0000", "size": 4}));
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["meta"]["variant_styles"]["Thing"]["color"], "#ff0000");
+        assert!(serde_json::to_value(Graph::new()).unwrap()["meta"].get("variant_styles").is_none());
+    }
+
+
+    #[test]
+    fn collapse_by_aggregates_groups_and_edges() {
+        let mut graph = Graph::new();
+        for (a, b) in [(1, 2), (1, 3), (4, 3)] {
+            graph.source_edge_target(&thing_of("Left", a), &thing_of("Right", b), "link", ()).unwrap();
+        }
+        graph.source_edge_target(&thing_of("Right", 2), &thing_of("Right", 3), "link", ()).unwrap();
+        graph.source_edge_target(&thing_of("Right", 3), &thing_of("Left", 1), "link", ()).unwrap();
+        let coarse = graph.collapse_by(|node| node["variant"].as_str().unwrap().to_string());
+        assert_eq!(coarse.nodes["Group"].len(), 2);
+        assert_eq!(coarse.nodes["Group"]["Left"]["props"]["count"], 2);
+        assert_eq!(coarse.nodes["Group"]["Right"]["props"]["count"], 2);
+        let aggregates = &coarse.edges["aggregate"];
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(aggregates["Left|aggregate|Right"]["props"]["weight"], 3);
+        assert_eq!(aggregates["Right|aggregate|Left"]["source"], "Right");
+        assert_eq!(aggregates["Right|aggregate|Left"]["props"]["weight"], 1);
+        assert_eq!(aggregates["Right|aggregate|Left"]["label"], "aggregate");
+        assert!(coarse.validate().is_ok());
+    }
+
+
+    #[test]
+    fn to_value_infallible_matches_to_node_json() {
+        let node = thing(1).to_node();
+        assert_eq!(node.to_value_infallible(), node.to_node_json().unwrap());
+        let node = Node::new("Review", 1, "Review|1".to_string(), "review".to_string(), Props::new().set("stars", 4));
+        assert_eq!(node.to_value_infallible()["props"]["stars"], 4);
+        let node = Node::new("Review", (1, "en"), "Review|(1, \"en\")".to_string(), "review".to_string(), ());
+        assert_eq!(node.to_value_infallible()["variant_pk"], serde_json::json!([1, "en"]));
+    }
+
+
+    #[test]
+    fn induced_subgraph_drops_edges_leaving_the_set() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4)]);
+        let ids: HashSet<String> = [id(1), id(2), id(3), "Thing|99".to_string()].into_iter().collect();
+        let subgraph = graph.induced_subgraph(&ids);
+        assert_eq!(subgraph.nodes["Thing"].len(), 3);
+        assert_eq!(subgraph.edges["link"].len(), 3);
+        assert!(!subgraph.edges["link"].contains_key(&edge_id(3, 4)));
+        assert!(subgraph.validate().is_ok());
+    }
+
+    #[test]
+    fn induced_subgraph_carries_meta_and_kept_hyperedges() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4)]);
+        graph.set_variant_style("Thing", serde_json::json!({"color": "#ff0000"}));
+        graph.meta.zoom_id = Some(id(1));
+        graph.layout_seed = Some(7);
+        graph.add_hyperedge(&Coauthorship(vec![1, 2]).to_hyperedge()).unwrap();
+        graph.add_hyperedge(&Coauthorship(vec![2, 4]).to_hyperedge()).unwrap();
+        let ids: HashSet<String> = [id(1), id(2), id(3)].into_iter().collect();
+        let subgraph = graph.induced_subgraph(&ids);
+        assert_eq!(subgraph.meta, graph.meta);
+        assert_eq!(subgraph.layout_seed, Some(7));
+        let hyperedges: Vec<&String> = subgraph.hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|[1, 2]"]);
+        assert!(subgraph.validate().is_ok());
+        let ids: HashSet<String> = [id(3), id(4)].into_iter().collect();
+        let subgraph = graph.induced_subgraph(&ids);
+        assert_eq!(subgraph.meta.zoom_id, None);
+        assert_eq!(subgraph.meta.variant_styles, graph.meta.variant_styles);
+        assert!(subgraph.hyperedges.is_empty());
+    }
+
+
+    #[test]
+    fn merge_newest_keeps_the_newer_node() {
+        let versioned = |name: &str, updated_at: Option<i64>| {
+            let mut graph = Graph::new();
+            let node = Node{updated_at, ..Node::new("Review", 1, "Review|1".to_string(), name.to_string(), ())};
+            graph.add_node(&node).unwrap();
+            graph
+        };
+        let mut graph = versioned("new", Some(200));
+        graph.merge_newest(versioned("old", Some(100)));
+        assert_eq!(graph.node_name("Review", "Review|1"), Some("new"));
+        graph.merge_newest(versioned("newer", Some(300)));
+        assert_eq!(graph.node_name("Review", "Review|1"), Some("newer"));
+        graph.merge_newest(versioned("undated", None));
+        assert_eq!(graph.node_name("Review", "Review|1"), Some("undated"));
+        assert_eq!(versioned("x", Some(5)).nodes["Review"]["Review|1"]["updated_at"], 5);
+    }
+
+
+    #[test]
+    fn incident_edge_variants_counts_each_variant() {
+        let mut graph = graph_from_edges(&[(1, 2), (3, 1)]);
+        graph.source_edge_target(&thing(1), &thing(4), "cites", ()).unwrap();
+        graph.source_edge_target(&thing(2), &thing(3), "cites", ()).unwrap();
+        let counts = graph.incident_edge_variants(&id(1));
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["link"], 2);
+        assert_eq!(counts["cites"], 1);
+        assert!(graph.incident_edge_variants("Thing|99").is_empty());
+    }
+
+
+    #[test]
+    fn common_neighbors_intersects_neighbor_sets() {
+        let graph = graph_from_edges(&[(1, 3), (4, 1), (2, 3), (2, 5), (1, 2)]);
+        assert_eq!(graph.common_neighbors(&id(1), &id(2)), vec![id(3)]);
+        assert!(graph.common_neighbors(&id(4), &id(5)).is_empty());
+    }
+
+
+    #[test]
+    fn jaccard_similarity_of_neighbor_sets() {
+        let graph = graph_from_edges(&[(1, 3), (1, 4), (2, 3), (2, 4), (5, 3), (5, 6)]);
+        assert_eq!(graph.jaccard_similarity(&id(1), &id(2)), 1.0);
+        assert!((graph.jaccard_similarity(&id(1), &id(5)) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(graph.jaccard_similarity("Thing|98", "Thing|99"), 0.0);
+    }
+
+
+    #[test]
+    fn auto_bundle_uses_the_edge_variant() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.source_edge_target(&thing(1), &thing(3), "cites", ()).unwrap();
+        assert!(graph.edges["link"][&edge_id(1, 2)].get("bundle").is_none());
+        graph.auto_bundle_by_variant();
+        for (variant, collection) in &graph.edges {
+            for edge in collection.values() {
+                assert_eq!(edge["bundle"], variant.as_str());
+            }
+        }
+    }
+
+
+    #[test]
+    fn nodes_sorted_by_a_numeric_prop() {
+        let mut graph = Graph::new();
+        for (pk, score) in [(1, Some(2.5)), (2, None), (3, Some(9.0)), (4, Some(-1.0))] {
+            graph.add_node_from(&thing(pk)).unwrap();
+            if let Some(score) = score {
+                graph.nodes.get_mut("Thing").unwrap().get_mut(&id(pk)).unwrap()["props"] = serde_json::json!({"score": score});
+            }
+        }
+        let score = |node: &serde_json::Value| node["props"]["score"].as_f64().unwrap_or(f64::NAN);
+        let ids = |sorted: Vec<(String, String)>| sorted.into_iter().map(|(_variant, id)| id).collect::<Vec<String>>();
+        assert_eq!(ids(graph.nodes_sorted_by(score, false)), vec![id(4), id(1), id(3), id(2)]);
+        assert_eq!(ids(graph.nodes_sorted_by(score, true)), vec![id(3), id(1), id(4), id(2)]);
+    }
+
+
+    #[test]
+    fn missing_edges_lists_unconnected_pairs() {
+        let mut graph = graph_from_edges(&[(2, 1)]);
+        graph.add_node_from(&thing(3)).unwrap();
+        graph.source_edge_target(&thing(3), &thing_of("Other", 1), "link", ()).unwrap();
+        assert_eq!(graph.missing_edges("Thing"), vec![(id(1), id(3)), (id(2), id(3))]);
+        assert!(graph.missing_edges("Other").is_empty());
+        assert!(graph.missing_edges("Nothing").is_empty());
+    }
+
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn new_stamps_generated_at() {
+        let graph = Graph::new();
+        // after 2020-01-01 and before 2100-01-01 
+        assert!(graph.generated_at > 1_577_836_800 && graph.generated_at < 4_102_444_800);
+        assert_eq!(graph.age_seconds(graph.generated_at + 90), 90);
+        let json = serde_json::to_value(&graph).unwrap();
+        assert_eq!(json["generated_at"], graph.generated_at);
+        let old: Graph = serde_json::from_str(r#"{"nodes": {}, "edges": {}}"#).unwrap();
+        assert_eq!(old.generated_at, 0);
+    }
+
+
+    #[test]
+    fn parallel_edges_collapse_into_a_weighted_edge() {
+        let mut graph = graph_from_edges(&[(2, 1)]);
+        for pk in 1..=3 {
+            graph.source_edge_target_with_pk(&thing(1), &thing(2), "link", pk, ()).unwrap();
+        }
+        graph.source_edge_target(&thing(1), &thing(2), "cites", ()).unwrap();
+        graph.collapse_parallel_edges();
+        let forward: Vec<&serde_json::Value> = graph.edges["link"].values().filter(|edge| edge["source"] == id(1)).collect();
+        assert_eq!(forward.len(), 1);
+        assert_eq!(forward[0]["weight"], 3);
+        assert_eq!(graph.edges["link"][&edge_id(2, 1)]["weight"], 1);
+        assert_eq!(graph.edges["cites"].len(), 1);
+    }
+
+
+    #[test]
+    fn node_badges_are_serialized_only_when_present() {
+        let json = Described.to_node().to_node_json().unwrap();
+        assert_eq!(json["badges"], serde_json::json!(["verified", "https://example.org/flag.svg"]));
+        assert!(thing(1).to_node().to_node_json().unwrap().get("badges").is_none());
+    }
+
+
+    #[test]
+    fn id_collisions_across_variants_are_reported() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        let shared = graph.nodes["Thing"][&id(1)].clone();
+        graph.nodes.entry("Other".to_string()).or_default().insert(id(1), shared);
+        graph.add_node_from(&thing_of("Other", 2)).unwrap();
+        assert_eq!(graph.id_collisions_across_variants(), vec![id(1)]);
+        assert!(graph_from_edges(&[(1, 2)]).id_collisions_across_variants().is_empty());
+    }
+
+
+    #[test]
+    fn renaming_a_variant_merges_into_the_target() {
+        let mut graph = Graph::with_index();
+        graph.source_edge_target(&thing_of("Old", 1), &thing(1), "link", ()).unwrap();
+        graph.source_edge_target(&thing_of("Old", 2), &thing(2), "cites", ()).unwrap();
+        graph.rename_node_variant("Old", "Thing");
+        assert!(!graph.nodes.contains_key("Old"));
+        assert_eq!(graph.nodes["Thing"].len(), 4);
+        assert_eq!(graph.nodes["Thing"]["Old|1"]["variant"], "Thing");
+        assert_eq!(graph.get_node_by_id("Old|2").unwrap()["variant"], "Thing");
+        graph.rename_edge_variant("cites", "link");
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.edges["link"].len(), 2);
+        assert!(graph.edges["link"].values().all(|edge| edge["variant"] == "link"));
+        graph.rename_node_variant("Missing", "Thing");
+        assert_eq!(graph.nodes["Thing"].len(), 4);
+    }
+
+
+    #[test]
+    fn symmetrize_balances_in_and_out_degree() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 1), (2, 3), (3, 4), (4, 4)]);
+        graph.source_edge_target(&thing(1), &thing(3), "cites", ()).unwrap();
+        assert_eq!((graph.out_degree(&id(3)), graph.in_degree(&id(3))), (1, 2));
+        graph.symmetrize();
+        for id in graph.degrees().keys() {
+            assert_eq!(graph.out_degree(id), graph.in_degree(id), "{}", id);
+        }
+        assert_eq!(graph.edges["link"].len(), 7);
+        let reverse = &graph.edges["link"][&format!("{}|reverse", edge_id(2, 3))];
+        assert_eq!((reverse["source"].as_str(), reverse["target"].as_str()), (Some(id(3).as_str()), Some(id(2).as_str())));
+        graph.edges.entry("odd".to_string()).or_default().insert("x".to_string(), serde_json::json!(["a", "b"]));
+        graph.symmetrize();
+        assert_eq!(graph.edges["link"].len(), 7);
+        assert_eq!(graph.edges["odd"].len(), 1);
+    }
+
+
+    #[test]
+    fn has_more_is_serialized_only_when_set() {
+        assert_eq!(Described.to_node().to_node_json().unwrap()["has_more"], true);
+        assert!(thing(1).to_node().to_node_json().unwrap().get("has_more").is_none());
+    }
+
+    #[test]
+    fn node_shape_is_serialized_only_when_set() {
+        assert_eq!(Described.to_node().to_node_json().unwrap()["shape"], "cube");
+        assert!(thing(1).to_node().to_node_json().unwrap().get("shape").is_none());
+    }
+
+
+    #[test]
+    fn positions_round_trip_with_apply_positions() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        let node = graph.nodes.get_mut("Thing").unwrap().get_mut(&id(3)).unwrap();
+        node["x"] = 7.0.into();
+        node["y"] = 8.0.into();
+        node["z"] = 9.0.into();
+        let pinned: HashMap<String, (f64, f64, f64)> = [(id(1), (1.0, 2.0, 3.0))].into_iter().collect();
+        graph.apply_positions(&pinned);
+        let positions = graph.positions();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[&id(1)], (1.0, 2.0, 3.0));
+        assert_eq!(positions[&id(3)], (7.0, 8.0, 9.0));
+        assert!(!positions.contains_key(&id(2)));
+    }
+
+
+    #[test]
+    fn nodes_without_names_are_reported() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        assert!(graph.assert_all_named().is_ok());
+        graph.nodes.get_mut("Thing").unwrap().get_mut(&id(3)).unwrap()["name"] = "".into();
+        graph.nodes.get_mut("Thing").unwrap().get_mut(&id(1)).unwrap().as_object_mut().unwrap().remove("name");
+        assert_eq!(graph.nodes_missing_names(), vec![id(1), id(3)]);
+        match graph.assert_all_named() {
+            Err(GraphError::UnnamedNodes(ids)) => assert_eq!(ids, vec![id(1), id(3)]),
+            other => panic!("expected UnnamedNodes, got {:?}", other),
+        }
+    }
+
+
+    #[test]
+    fn prefixed_graphs_merge_without_collisions() {
+        let mut crm = graph_from_edges(&[(1, 2), (2, 3)]);
+        let mut billing = graph_from_edges(&[(1, 2)]);
+        for graph in [&mut crm, &mut billing] {
+            graph.add_hyperedge(&Coauthorship(vec![1, 2]).to_hyperedge()).unwrap();
+            graph.meta.zoom_id = Some(id(1));
+        }
+        crm.prefix_ids("crm:");
+        billing.prefix_ids("billing:");
+        let hyperedge = &crm.hyperedges["coauthored"]["crm:coauthored|[1, 2]"];
+        assert_eq!(hyperedge["id"], "crm:coauthored|[1, 2]");
+        assert_eq!(hyperedge["members"], serde_json::json!([format!("crm:{}", id(1)), format!("crm:{}", id(2))]));
+        assert_eq!(crm.meta.zoom_id, Some(format!("crm:{}", id(1))));
+        assert!(crm.nodes["Thing"].keys().all(|id| id.starts_with("crm:")));
+        assert!(crm.nodes["Thing"].values().all(|node| node["id"].as_str().unwrap().starts_with("crm:")));
+        assert!(!crm.contains_node(&id(1)));
+        assert!(crm.validate().is_ok());
+        assert_eq!(crm.edges["link"][&format!("crm:{}", edge_id(1, 2))]["source"], format!("crm:{}", id(1)));
+        crm.merge(billing);
+        assert_eq!(crm.nodes["Thing"].len(), 5);
+        assert_eq!(crm.edges["link"].len(), 3);
+        assert_eq!(crm.hyperedges["coauthored"].len(), 2);
+        assert!(crm.validate().is_ok());
+
+        let mut odd: Graph = serde_json::from_str(r#"{"nodes": {"odd": {"x": 5}}, "edges": {"odd": {"y": null}}}"#).unwrap();
+        odd.prefix_ids("crm:");
+        assert_eq!(odd.nodes["odd"]["crm:x"], 5);
+        assert!(odd.edges["odd"]["crm:y"].is_null());
+    }
+
+    #[test]
+    fn node_pages_cover_the_variant_without_overlap() {
+        let graph = Graph::from_nodes((1..=7).map(thing)).unwrap();
+        let total = graph.node_count("Thing");
+        assert_eq!(total, 7);
+        let mut seen: Vec<String> = Vec::new();
+        for page in 0..total.div_ceil(3) {
+            let ids: Vec<String> = graph.node_page("Thing", page * 3, 3).into_iter().map(|(id, _node)| id).collect();
+            assert!(ids.len() <= 3);
+            seen.extend(ids);
+        }
+        let mut expected: Vec<String> = (1..=7).map(id).collect();
+        expected.sort();
+        assert_eq!(seen, expected);
+        assert!(graph.node_page("Thing", 7, 3).is_empty());
+        assert_eq!(graph.node_page("Thing", 0, 1)[0].1["name"], "Thing 1");
+        assert_eq!(graph.node_count("Missing"), 0);
+    }
+
+    #[test]
+    fn toggling_one_edge_direction_leaves_the_rest() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        assert!(graph.edges["link"][&edge_id(1, 2)].get("directed").is_none());
+        graph.set_all_edges_directed(true);
+        graph.set_edge_directed("link", &edge_id(2, 3), false).unwrap();
+        assert_eq!(graph.edges["link"][&edge_id(1, 2)]["directed"], true);
+        assert_eq!(graph.edges["link"][&edge_id(2, 3)]["directed"], false);
+        assert!(matches!(graph.set_edge_directed("link", "nope", true), Err(GraphError::EdgeNotFound(id)) if id == "nope"));
+        assert!(matches!(graph.set_edge_directed("cites", &edge_id(1, 2), true), Err(GraphError::EdgeNotFound(_))));
+    }
+
+    #[test]
+    fn setting_direction_skips_non_object_edges() {
+        let mut graph: Graph = serde_json::from_str(r#"{"nodes": {}, "edges": {"odd": {"x": 5}}}"#).unwrap();
+        graph.set_all_edges_directed(true);
+        graph.set_edge_directed("odd", "x", false).unwrap();
+        assert_eq!(graph.edges["odd"]["x"], 5);
+    }
+
+    #[test]
+    fn non_finite_props_never_reach_the_json() {
+        let mut graph = Graph::new();
+        let node = Node::new("Review", 1, "Review|1".to_string(), "review".to_string(), vec![1.5, f64::NAN, f64::INFINITY]);
+        graph.add_node(&node).unwrap();
+        // the NaN and infinity were already nulled when the node was converted to JSON, so sanitize has nothing left to fix 
+        assert_eq!(graph.nodes["Review"]["Review|1"]["props"], serde_json::json!([1.5, null, null]));
+        assert_eq!(graph.sanitize(), 0);
+        assert!(serde_json::to_string(&graph).is_ok());
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn null_non_finite_replaces_out_of_range_literals() {
+        let mut value: serde_json::Value = serde_json::from_str(r#"{"a": [1e999, 2.5, -1e999], "b": {"c": 1e400}, "d": 7, "e": 18446744073709551616}"#).unwrap();
+        assert_eq!(null_non_finite(&mut value), 3);
+        assert_eq!(value["a"], serde_json::json!([null, 2.5, null]));
+        assert!(value["b"]["c"].is_null());
+        assert_eq!(value["d"], 7);
+        assert!(value["e"].is_number());
+        let mut graph: Graph = serde_json::from_str(r#"{"nodes": {"Thing": {"Thing|1": {"id": "Thing|1", "name": "a", "x": 1e999}}}, "edges": {}}"#).unwrap();
+        assert_eq!(graph.sanitize(), 1);
+        assert!(graph.nodes["Thing"]["Thing|1"]["x"].is_null());
+        assert_eq!(graph.sanitize(), 0);
+    }
+
+    #[test]
+    fn between_sets_keeps_only_crossing_edges() {
+        let graph = graph_from_edges(&[(1, 2), (1, 3), (4, 2), (3, 4), (2, 5)]);
+        let a: HashSet<String> = [id(1), id(2)].into_iter().collect();
+        let b: HashSet<String> = [id(3), id(4)].into_iter().collect();
+        let between = graph.between_sets(&a, &b);
+        assert_eq!(between.nodes["Thing"].len(), 4);
+        let mut edges: Vec<&String> = between.edges["link"].keys().collect();
+        edges.sort();
+        assert_eq!(edges, vec![&edge_id(1, 3), &edge_id(4, 2)]);
+
+        let mut graph = graph;
+        graph.layout_seed = Some(3);
+        graph.set_variant_style("Thing", serde_json::json!({"size": 2}));
+        graph.add_hyperedge(&Coauthorship(vec![1, 3]).to_hyperedge()).unwrap();
+        graph.add_hyperedge(&Coauthorship(vec![1, 5]).to_hyperedge()).unwrap();
+        let between = graph.between_sets(&a, &b);
+        assert_eq!(between.layout_seed, Some(3));
+        assert_eq!(between.meta, graph.meta);
+        let hyperedges: Vec<&String> = between.hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|[1, 3]"]);
+    }
+
+    #[derive(Debug)]
+    struct TicketPk {
+        number: u32,
+    }
+
+    struct Ticket {
+        stable: bool,
+    }
+
+    impl ToEdge<&'static str, TicketPk, ()> for Ticket {
+        fn edge_variant(&self) -> &'static str {
+            "ticket"
+        }
+        fn edge_pk(&self) -> TicketPk {
+            TicketPk{number: 5}
+        }
+        fn edge_pk_string(&self) -> String {
+            if self.stable { self.edge_pk().number.to_string() } else { format!("{:?}", self.edge_pk()) }
+        }
+        fn edge_source(&self) -> String {
+            id(1)
+        }
+        fn edge_target(&self) -> String {
+            id(2)
+        }
+        fn edge_props(&self) {}
+    }
+
+    #[test]
+    fn edge_pk_string_controls_the_edge_id() {
+        assert_eq!(Review.to_edge().id, "reviewed|1");
+        assert_eq!(Ticket{stable: false}.edge_id(), "ticket|TicketPk { number: 5 }");
+        assert_eq!(Ticket{stable: true}.edge_id(), "ticket|5");
+    }
+
+    #[test]
+    fn node_decorator_runs_on_insertion() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.with_node_decorator(|node| node["tenant"] = "acme".into());
+        graph.add_node_from(&thing(3)).unwrap();
+        link(&mut graph, 3, 4);
+        assert_eq!(graph.nodes["Thing"][&id(3)]["tenant"], "acme");
+        assert_eq!(graph.nodes["Thing"][&id(4)]["tenant"], "acme");
+        assert!(graph.nodes["Thing"][&id(1)].get("tenant").is_none());
+        graph.decorate_all_now();
+        assert!(graph.nodes["Thing"].values().all(|node| node["tenant"] == "acme"));
+    }
+
+    #[test]
+    fn edge_endpoints_returns_both_nodes() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        let (source, target) = graph.edge_endpoints("link", &edge_id(1, 2)).unwrap();
+        assert_eq!((source["id"].as_str(), target["id"].as_str()), (Some(id(1).as_str()), Some(id(2).as_str())));
+        graph.nodes.get_mut("Thing").unwrap().remove(&id(3));
+        assert!(graph.edge_endpoints("link", &edge_id(2, 3)).is_none());
+        assert!(graph.edge_endpoints("link", "nope").is_none());
+        assert!(graph.edge_endpoints("cites", &edge_id(1, 2)).is_none());
+    }
+
+    #[test]
+    fn add_node_merge_keeps_fields_from_other_passes() {
+        let mut graph = Graph::new();
+        let first = Node::new("Review", 1, "Review|1".to_string(), "draft".to_string(), Props::new().set("stars", 4).set("lang", "en"));
+        graph.add_node_merge(&first).unwrap();
+        graph.nodes.get_mut("Review").unwrap().get_mut("Review|1").unwrap()["x"] = 12.5.into();
+        let second = Node{name: "final".to_string(), props: Props::new().set("stars", 5), ..first};
+        graph.add_node_merge(&second).unwrap();
+        let node = &graph.nodes["Review"]["Review|1"];
+        assert_eq!(node["name"], "final");
+        assert_eq!(node["x"], 12.5);
+        assert_eq!(node["props"], serde_json::json!({"stars": 5, "lang": "en"}));
+        graph.add_node(&second).unwrap();
+        assert!(graph.nodes["Review"]["Review|1"].get("x").is_none());
+    }
+
+    #[test]
+    fn self_loops_are_reported_and_removed() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 2), (2, 3)]);
+        assert_eq!(graph.self_loops(), vec![edge_id(2, 2)]);
+        assert_eq!(graph.remove_self_loops(), 1);
+        assert!(graph.self_loops().is_empty());
+        assert_eq!(graph.edges["link"].len(), 2);
+    }
+
+    #[test]
+    fn cap_node_degree_trims_a_hub() {
+        let spokes: Vec<(i32, i32)> = (1..=10).map(|pk| (0, pk)).collect();
+        let mut graph = graph_from_edges(&spokes);
+        link(&mut graph, 1, 2);
+        assert_eq!(graph.degrees()[&id(0)], 10);
+        graph.cap_node_degree(3);
+        assert!(graph.degrees().values().all(|degree| *degree <= 3));
+        assert_eq!(graph.degrees()[&id(0)], 3);
+        let mut kept: Vec<&String> = graph.edges["link"].keys().collect();
+        kept.sort();
+        assert_eq!(kept, vec![&edge_id(0, 1), &edge_id(0, 10), &edge_id(0, 2), &edge_id(1, 2)]);
+        assert_eq!(graph.nodes["Thing"].len(), 11);
+    }
+
+    #[test]
+    fn from_tree_links_children_to_parents() {
+        let row = |id: &str, parent: Option<&str>| (id.to_string(), parent.map(str::to_string), format!("Category {}", id));
+        let rows = vec![row("root", None), row("a", Some("root")), row("b", Some("root")), row("a1", Some("a")), row("a2", Some("a"))];
+        let graph = Graph::from_tree(rows, "Category", "child-of").unwrap();
+        assert_eq!(graph.nodes["Category"].len(), 5);
+        assert_eq!(graph.edges["child-of"].len(), 4);
+        assert_eq!(graph.edges["child-of"]["\"a1\"|child-of|\"a\""]["target"], "a");
+        assert_eq!(graph.edges["child-of"]["\"a1\"|child-of|\"a\""]["label"], "child-of");
+        assert_eq!(graph.nodes["Category"]["a2"]["name"], "Category a2");
+        let orphan = Graph::from_tree(vec![row("x", Some("missing"))], "Category", "child-of");
+        assert!(matches!(orphan, Err(GraphError::DanglingEdges(ids)) if ids == vec!["\"x\"|child-of|\"missing\"".to_string()]));
+
+        let mut linked = Graph::new();
+        linked.source_edge_target(&Category("a1"), &Category("a"), "child-of", ()).unwrap();
+        let mut merged = graph;
+        merged.merge(linked);
+        assert_eq!(merged.edges["child-of"].len(), 4);
+    }
+
+    struct Category(&'static str);
+
+    impl ToNode<&'static str, &'static str, ()> for Category {
+        fn node_variant(&self) -> &'static str {
+            "Category"
+        }
+        fn node_pk(&self) -> &'static str {
+            self.0
+        }
+        fn node_id(&self) -> String {
+            self.0.to_string()
+        }
+        fn node_name(&self) -> String {
+            format!("Category {}", self.0)
+        }
+        fn node_props(&self) {}
+    }
+}
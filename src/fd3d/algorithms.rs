@@ -0,0 +1,990 @@
+//! Graph-theoretic analyses of a Graph. Unless stated otherwise these treat edges as undirected
+//! and ignore edges whose source or target node is missing from the graph 
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use super::{Graph, GraphError, edge_ends};
+
+
+/// An index-based view of a Graph for the algorithms below: nodes are numbered in sorted id order and
+/// edges in sorted (variant, id) order, so results do not depend on HashMap iteration order 
+struct Indexed<'a> {
+    ids: Vec<&'a str>,
+    /// (source index, target index, edge id) for each edge between two present nodes 
+    edges: Vec<(usize, usize, &'a str)>,
+    /// the variant of each edge in edges 
+    edge_variants: Vec<&'a str>,
+    /// for each node, the (neighbor index, edge index) of every incident edge other than self-loops 
+    adjacency: Vec<Vec<(usize, usize)>>,
+}
+
+/// The result of Indexed::low_links 
+struct LowLinks {
+    /// the order in which each node was discovered 
+    disc: Vec<usize>,
+    /// the earliest discovery time reachable from each node's DFS subtree plus one back edge 
+    low: Vec<usize>,
+    /// (parent, child, edge index) for each edge of the DFS forest 
+    tree: Vec<(usize, usize, usize)>,
+}
+
+impl<'a> Indexed<'a> {
+    fn new(graph: &'a Graph) -> Self {
+        let mut ids: Vec<&str> = graph.nodes.values().flat_map(|collection| collection.keys().map(|id| id.as_str())).collect();
+        ids.sort();
+        ids.dedup();
+        let index: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let mut keyed: Vec<(&str, &str, &serde_json::Value)> = graph.edges.iter()
+            .flat_map(|(variant, collection)| collection.iter().map(move |(id, edge)| (variant.as_str(), id.as_str(), edge)))
+            .collect();
+        keyed.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        let mut edges = Vec::new();
+        let mut edge_variants = Vec::new();
+        let mut adjacency = vec![Vec::new(); ids.len()];
+        for (variant, id, edge) in keyed {
+            let (source, target) = edge_ends(edge);
+            if let (Some(&s), Some(&t)) = (index.get(source), index.get(target)) {
+                let e = edges.len();
+                edges.push((s, t, id));
+                edge_variants.push(variant);
+                if s != t {
+                    adjacency[s].push((t, e));
+                    adjacency[t].push((s, e));
+                }
+            }
+        }
+        Indexed{ids, edges, edge_variants, adjacency}
+    }
+
+    /// the distinct neighbors of each node 
+    fn neighbor_sets(&self) -> Vec<HashSet<usize>> {
+        self.adjacency.iter().map(|adjacent| adjacent.iter().map(|(v, _e)| *v).collect()).collect()
+    }
+
+    /// for each node, the targets of its outgoing edges (self-loops included) 
+    fn out_adjacency(&self) -> Vec<Vec<usize>> {
+        let mut out = vec![Vec::new(); self.ids.len()];
+        for (s, t, _id) in &self.edges {
+            out[*s].push(*t);
+        }
+        out
+    }
+
+    /// the connected components as lists of node indices, in order of their smallest id (each list is sorted) 
+    fn components(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.ids.len()];
+        let mut components = Vec::new();
+        for start in 0..self.ids.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(u) = queue.pop_front() {
+                for (v, _e) in &self.adjacency[u] {
+                    if !seen[*v] {
+                        seen[*v] = true;
+                        component.push(*v);
+                        queue.push_back(*v);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        components
+    }
+
+    /// Depth-first search recording each node's discovery time and low-link value, as used by
+    /// Tarjan's bridge and articulation point algorithms.
+    /// The search is iterative so deep graphs can't overflow the stack 
+    fn low_links(&self) -> LowLinks {
+        let n = self.ids.len();
+        let mut disc = vec![usize::MAX; n];
+        let mut low = vec![usize::MAX; n];
+        let mut tree = Vec::new();
+        let mut timer = 0;
+        for root in 0..n {
+            if disc[root] != usize::MAX {
+                continue;
+            }
+            disc[root] = timer;
+            low[root] = timer;
+            timer += 1;
+            // (node, the edge we arrived by, position in its adjacency list) 
+            let mut stack: Vec<(usize, Option<usize>, usize)> = vec![(root, None, 0)];
+            while let Some(top) = stack.len().checked_sub(1) {
+                let (u, arrived_by, next) = stack[top];
+                if next < self.adjacency[u].len() {
+                    stack[top].2 += 1;
+                    let (v, e) = self.adjacency[u][next];
+                    if Some(e) == arrived_by {
+                        continue;
+                    }
+                    if disc[v] == usize::MAX {
+                        disc[v] = timer;
+                        low[v] = timer;
+                        timer += 1;
+                        tree.push((u, v, e));
+                        stack.push((v, Some(e), 0));
+                    } else {
+                        low[u] = low[u].min(disc[v]);
+                    }
+                } else {
+                    stack.pop();
+                    if let Some(&(parent, _, _)) = stack.last() {
+                        low[parent] = low[parent].min(low[u]);
+                    }
+                }
+            }
+        }
+        LowLinks{disc, low, tree}
+    }
+}
+
+
+/// The longest path, in edges, that Graph::all_paths will search for 
+pub const ALL_PATHS_MAX_LEN: usize = 10;
+
+
+/// depth-first extension of path towards end for Graph::all_paths 
+fn extend_paths(neighbors: &[Vec<usize>], end: usize, max_len: usize, path: &mut Vec<usize>, on_path: &mut [bool], paths: &mut Vec<Vec<usize>>) {
+    let last = path[path.len() - 1];
+    if last == end {
+        if path.len() > 1 {
+            paths.push(path.clone());
+        }
+        return;
+    }
+    if path.len() > max_len {
+        return;
+    }
+    for next in &neighbors[last] {
+        if !on_path[*next] {
+            on_path[*next] = true;
+            path.push(*next);
+            extend_paths(neighbors, end, max_len, path, on_path, paths);
+            path.pop();
+            on_path[*next] = false;
+        }
+    }
+}
+
+
+impl Graph {
+    /// The ids of the bridges (cut edges): edges whose removal would disconnect their two endpoints.
+    /// Found with Tarjan's bridge-finding algorithm in O(V + E). Parallel edges are never bridges 
+    pub fn bridges(&self) -> Vec<String> {
+        let indexed = Indexed::new(self);
+        let LowLinks{disc, low, tree} = indexed.low_links();
+        let mut bridges: Vec<String> = tree.into_iter()
+            .filter(|(parent, child, _e)| low[*child] > disc[*parent])
+            .map(|(_parent, _child, e)| indexed.edges[e].2.to_string())
+            .collect();
+        bridges.sort();
+        bridges
+    }
+
+
+    /// The ids of the articulation points: nodes whose removal would split their connected component.
+    /// Uses the same DFS low-link computation as bridges, in O(V + E) 
+    pub fn articulation_points(&self) -> Vec<String> {
+        let indexed = Indexed::new(self);
+        let LowLinks{disc, low, tree} = indexed.low_links();
+        let mut has_parent = vec![false; indexed.ids.len()];
+        let mut children = vec![0usize; indexed.ids.len()];
+        let mut cut = vec![false; indexed.ids.len()];
+        for (parent, child, _e) in &tree {
+            has_parent[*child] = true;
+            children[*parent] += 1;
+            if low[*child] >= disc[*parent] {
+                cut[*parent] = true;
+            }
+        }
+        // a DFS root is only a cut vertex if it has more than one child in the DFS tree 
+        indexed.ids.iter().enumerate()
+            .filter(|(i, _id)| if has_parent[*i] { cut[*i] } else { children[*i] > 1 })
+            .map(|(_i, id)| id.to_string())
+            .collect()
+    }
+
+
+    /// The local clustering coefficient of each node: the fraction of pairs of its neighbors that are themselves adjacent.
+    /// Nodes with fewer than two neighbors get 0.0 
+    pub fn clustering_coefficients(&self) -> HashMap<String, f64> {
+        let indexed = Indexed::new(self);
+        let neighbors = indexed.neighbor_sets();
+        indexed.ids.iter().enumerate()
+            .map(|(u, id)| {
+                let adjacent: Vec<usize> = neighbors[u].iter().copied().collect();
+                let k = adjacent.len();
+                let coefficient = if k < 2 {
+                    0.0
+                } else {
+                    let mut links = 0;
+                    for (i, a) in adjacent.iter().enumerate() {
+                        links += adjacent[i + 1..].iter().filter(|b| neighbors[*a].contains(b)).count();
+                    }
+                    links as f64 / (k * (k - 1) / 2) as f64
+                };
+                (id.to_string(), coefficient)
+            })
+            .collect()
+    }
+
+
+    /// The connected components (ignoring edge direction) as lists of node ids.
+    /// Each list is sorted and the components are ordered by their smallest id 
+    pub fn connected_components(&self) -> Vec<Vec<String>> {
+        let indexed = Indexed::new(self);
+        indexed.components().into_iter()
+            .map(|component| component.into_iter().map(|i| indexed.ids[i].to_string()).collect())
+            .collect()
+    }
+
+
+    /// Remove every node and edge outside the largest connected component, and the hyperedge members outside it.
+    /// If several components share the largest size, the one with the smallest id is kept 
+    pub fn retain_largest_component(&mut self) {
+        let components = self.connected_components();
+        let mut largest: Option<&Vec<String>> = None;
+        for component in &components {
+            if largest.is_none_or(|l| component.len() > l.len()) {
+                largest = Some(component);
+            }
+        }
+        let keep: HashSet<&str> = largest.into_iter().flatten().map(String::as_str).collect();
+        for collection in self.nodes.values_mut() {
+            collection.retain(|id, _node| keep.contains(id.as_str()));
+        }
+        self.drop_dangling_edges();
+        self.reindex();
+    }
+
+
+    /// A coarse community id per node from label propagation: every node starts with its own label and, for up to
+    /// iterations rounds, adopts the label most common among its neighbors. Updates are synchronous and ties go to the
+    /// smallest label, so results are reproducible. The ids themselves carry no meaning beyond grouping nodes 
+    pub fn label_propagation(&self, iterations: usize) -> HashMap<String, u64> {
+        let indexed = Indexed::new(self);
+        let neighbors = indexed.neighbor_sets();
+        let mut labels: Vec<u64> = (0..indexed.ids.len() as u64).collect();
+        for _ in 0..iterations {
+            let next: Vec<u64> = (0..labels.len())
+                .map(|u| {
+                    let mut counts: HashMap<u64, usize> = HashMap::new();
+                    for v in &neighbors[u] {
+                        *counts.entry(labels[*v]).or_default() += 1;
+                    }
+                    counts.into_iter()
+                        .max_by(|(label_a, count_a), (label_b, count_b)| count_a.cmp(count_b).then(label_b.cmp(label_a)))
+                        .map_or(labels[u], |(label, _count)| label)
+                })
+                .collect();
+            if next == labels {
+                break;
+            }
+            labels = next;
+        }
+        indexed.ids.iter().zip(labels).map(|(id, label)| (id.to_string(), label)).collect()
+    }
+
+
+    /// Partition the graph into one self-contained Graph per connected component, ordered as connected_components.
+    /// Each component keeps the hyperedges whose members all lie in it, along with meta (zoom_id only in the
+    /// component holding that node) and layout_seed. Dangling edges, which belong to no component, are dropped 
+    pub fn split_components(&self) -> Vec<Graph> {
+        let indexed = Indexed::new(self);
+        let components = indexed.components();
+        let mut component_of: HashMap<&str, usize> = HashMap::new();
+        for (c, component) in components.iter().enumerate() {
+            for i in component {
+                component_of.insert(indexed.ids[*i], c);
+            }
+        }
+        let mut graphs: Vec<Graph> = components.iter().map(|_| Graph::new()).collect();
+        for (variant, collection) in &self.nodes {
+            for (id, node) in collection {
+                let graph = &mut graphs[component_of[id.as_str()]];
+                graph.nodes.entry(variant.clone()).or_default().insert(id.clone(), node.clone());
+            }
+        }
+        for (variant, collection) in &self.edges {
+            for (id, edge) in collection {
+                let (source, target) = edge_ends(edge);
+                if let (Some(c), true) = (component_of.get(source), component_of.contains_key(target)) {
+                    graphs[*c].edges.entry(variant.clone()).or_default().insert(id.clone(), edge.clone());
+                }
+            }
+        }
+        for graph in &mut graphs {
+            self.carry_graph_state(graph);
+        }
+        graphs
+    }
+
+
+    /// How many nodes have each degree, from a single pass over the edges. Sorted by degree for plotting 
+    pub fn degree_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        for degree in self.degrees().into_values() {
+            *histogram.entry(degree).or_default() += 1;
+        }
+        histogram
+    }
+
+
+    /// PageRank of each node by power iteration, treating edges as directed and each of a node's out-edges as equally weighted.
+    /// The rank of nodes without out-edges is spread evenly over all nodes. damping is typically 0.85, and the ranks sum to 1 
+    pub fn pagerank(&self, damping: f64, iterations: usize) -> HashMap<String, f64> {
+        let indexed = Indexed::new(self);
+        let n = indexed.ids.len();
+        if n == 0 {
+            return HashMap::new();
+        }
+        let mut out_degree = vec![0usize; n];
+        for (s, _t, _id) in &indexed.edges {
+            out_degree[*s] += 1;
+        }
+        let mut ranks = vec![1.0 / n as f64; n];
+        for _ in 0..iterations {
+            let dangling: f64 = (0..n).filter(|u| out_degree[*u] == 0).map(|u| ranks[u]).sum();
+            let mut next = vec![(1.0 - damping) / n as f64 + damping * dangling / n as f64; n];
+            for (s, t, _id) in &indexed.edges {
+                next[*t] += damping * ranks[*s] / out_degree[*s] as f64;
+            }
+            ranks = next;
+        }
+        let total: f64 = ranks.iter().sum();
+        indexed.ids.iter().zip(ranks).map(|(id, rank)| (id.to_string(), rank / total)).collect()
+    }
+
+
+    /// A directed cycle in the graph, as the ids of the nodes along it in edge order, or None if the graph is a DAG 
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let indexed = Indexed::new(self);
+        let out = indexed.out_adjacency();
+        // 0 = unvisited, 1 = on the current DFS path, 2 = finished 
+        let mut state = vec![0u8; indexed.ids.len()];
+        for root in 0..indexed.ids.len() {
+            if state[root] != 0 {
+                continue;
+            }
+            state[root] = 1;
+            let mut stack: Vec<(usize, usize)> = vec![(root, 0)];
+            while let Some(top) = stack.len().checked_sub(1) {
+                let (u, next) = stack[top];
+                if next < out[u].len() {
+                    stack[top].1 += 1;
+                    let v = out[u][next];
+                    match state[v] {
+                        0 => {
+                            state[v] = 1;
+                            stack.push((v, 0));
+                        }
+                        1 => {
+                            let start = stack.iter().position(|(w, _)| *w == v).unwrap_or_default();
+                            return Some(stack[start..].iter().map(|(w, _)| indexed.ids[*w].to_string()).collect());
+                        }
+                        _ => {}
+                    }
+                } else {
+                    state[u] = 2;
+                    stack.pop();
+                }
+            }
+        }
+        None
+    }
+
+
+    /// Ok if the graph has no directed cycle, otherwise GraphError::Cycle listing one. Handy with `?` in pipelines that need a DAG 
+    pub fn assert_acyclic(&self) -> Result<(), GraphError> {
+        match self.find_cycle() {
+            Some(nodes) => Err(GraphError::Cycle{nodes}),
+            None => Ok(()),
+        }
+    }
+
+
+    /// Every simple path (no repeated node) between from and to of at most max_len edges, treating edges as undirected.
+    /// Each path lists node ids from from to to, and paths are sorted shortest first.
+    /// The number of paths can grow exponentially with max_len, so it is capped at ALL_PATHS_MAX_LEN 
+    pub fn all_paths(&self, from: &str, to: &str, max_len: usize) -> Vec<Vec<String>> {
+        let indexed = Indexed::new(self);
+        let neighbors: Vec<Vec<usize>> = indexed.neighbor_sets().into_iter()
+            .map(|set| {
+                let mut sorted: Vec<usize> = set.into_iter().collect();
+                sorted.sort();
+                sorted
+            })
+            .collect();
+        let (Ok(start), Ok(end)) = (indexed.ids.binary_search(&from), indexed.ids.binary_search(&to)) else {
+            return Vec::new();
+        };
+        let max_len = max_len.min(ALL_PATHS_MAX_LEN);
+        let mut paths = Vec::new();
+        let mut path = vec![start];
+        let mut on_path = vec![false; indexed.ids.len()];
+        on_path[start] = true;
+        extend_paths(&neighbors, end, max_len, &mut path, &mut on_path, &mut paths);
+        let mut paths: Vec<Vec<String>> = paths.into_iter()
+            .map(|path| path.into_iter().map(|i| indexed.ids[i].to_string()).collect())
+            .collect();
+        paths.sort_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)));
+        paths
+    }
+
+
+
+
+    /// Whether adding a directed edge source_id -> target_id would close a cycle, i.e. whether target_id already
+    /// reaches source_id along the edges' directions (or the edge would be a self-loop). A preflight check for editors
+    /// that must keep the graph acyclic; O(V + E) 
+    pub fn would_create_cycle(&self, source_id: &str, target_id: &str) -> bool {
+        if source_id == target_id {
+            return true;
+        }
+        let indexed = Indexed::new(self);
+        let (Ok(source), Ok(target)) = (indexed.ids.binary_search(&source_id), indexed.ids.binary_search(&target_id)) else {
+            return false;
+        };
+        let out = indexed.out_adjacency();
+        let mut seen = vec![false; indexed.ids.len()];
+        seen[target] = true;
+        let mut queue = VecDeque::from([target]);
+        while let Some(u) = queue.pop_front() {
+            for v in &out[u] {
+                if *v == source {
+                    return true;
+                }
+                if !seen[*v] {
+                    seen[*v] = true;
+                    queue.push_back(*v);
+                }
+            }
+        }
+        false
+    }
+
+
+
+
+    /// The diameter: the longest shortest path, in edges, between any two nodes, treating edges as undirected.
+    /// None if the graph is empty or disconnected; call it on the parts from split_components to measure each component.
+    /// Runs a BFS from every node, O(V * (V + E)), so it is meant for graphs of modest size 
+    pub fn diameter(&self) -> Option<usize> {
+        let indexed = Indexed::new(self);
+        let neighbors = indexed.neighbor_sets();
+        let n = indexed.ids.len();
+        if n == 0 {
+            return None;
+        }
+        let mut diameter = 0;
+        for start in 0..n {
+            let mut distance: Vec<Option<usize>> = vec![None; n];
+            distance[start] = Some(0);
+            let mut queue = VecDeque::from([start]);
+            let mut reached = 1;
+            while let Some(u) = queue.pop_front() {
+                let next = distance[u].map(|d| d + 1);
+                for v in &neighbors[u] {
+                    if distance[*v].is_none() {
+                        distance[*v] = next;
+                        diameter = diameter.max(next.unwrap_or_default());
+                        reached += 1;
+                        queue.push_back(*v);
+                    }
+                }
+            }
+            if reached < n {
+                return None;
+            }
+        }
+        Some(diameter)
+    }
+
+
+
+
+    /// Split the nodes into two sides such that every edge runs between the sides, by 2-colouring each connected
+    /// component with a BFS from its smallest id (which goes on the first side). None if the graph is not bipartite,
+    /// i.e. it has an odd cycle, counting self-loops 
+    pub fn bipartite_sides(&self) -> Option<(HashSet<String>, HashSet<String>)> {
+        let indexed = Indexed::new(self);
+        if indexed.edges.iter().any(|(s, t, _id)| s == t) {
+            return None;
+        }
+        let neighbors = indexed.neighbor_sets();
+        let mut side: Vec<Option<bool>> = vec![None; indexed.ids.len()];
+        for start in 0..indexed.ids.len() {
+            if side[start].is_some() {
+                continue;
+            }
+            side[start] = Some(false);
+            let mut queue = VecDeque::from([start]);
+            while let Some(u) = queue.pop_front() {
+                let other = side[u].map(|s| !s);
+                for v in &neighbors[u] {
+                    match side[*v] {
+                        None => {
+                            side[*v] = other;
+                            queue.push_back(*v);
+                        },
+                        Some(s) if Some(s) != other => return None,
+                        Some(_) => {},
+                    }
+                }
+            }
+        }
+        let (mut first, mut second) = (HashSet::new(), HashSet::new());
+        for (id, s) in indexed.ids.iter().zip(side) {
+            if s == Some(false) { &mut first } else { &mut second }.insert(id.to_string());
+        }
+        Some((first, second))
+    }
+
+
+
+
+    /// The ids of the leaves: nodes with exactly one incident edge (of any variant, in either direction), sorted.
+    /// Dangling edges count towards the degree of their present end, as in degrees 
+    pub fn leaf_nodes(&self) -> Vec<String> {
+        let mut leaves: Vec<String> = self.degrees().into_iter()
+            .filter(|(_id, degree)| *degree == 1)
+            .map(|(id, _degree)| id)
+            .collect();
+        leaves.sort();
+        leaves
+    }
+
+
+    /// Peel pendant nodes for an overview: each round removes every current leaf along with its edge,
+    /// so a path loses both ends per round and a tree shrinks towards its center. Stops early once there are no leaves 
+    pub fn trim_leaves(&mut self, rounds: usize) {
+        for _ in 0..rounds {
+            let leaves = self.leaf_nodes();
+            if leaves.is_empty() {
+                break;
+            }
+            for id in leaves {
+                self.remove_node(&id);
+            }
+        }
+    }
+
+
+
+
+    /// The k-core: the subgraph left after repeatedly removing every node with fewer than k distinct neighbors,
+    /// i.e. the largest induced subgraph in which every node has at least k. Parallel edges and self-loops do not
+    /// add to a node's count. Meta, layout_seed and the hyperedges inside the core carry over, as in induced_subgraph.
+    /// Runs in O(V + E) 
+    pub fn k_core(&self, k: usize) -> Graph {
+        let indexed = Indexed::new(self);
+        let neighbors = indexed.neighbor_sets();
+        let mut degree: Vec<usize> = neighbors.iter().map(HashSet::len).collect();
+        let mut removed = vec![false; indexed.ids.len()];
+        let mut queue: VecDeque<usize> = (0..indexed.ids.len()).filter(|u| degree[*u] < k).collect();
+        for u in &queue {
+            removed[*u] = true;
+        }
+        while let Some(u) = queue.pop_front() {
+            for v in &neighbors[u] {
+                if !removed[*v] {
+                    degree[*v] -= 1;
+                    if degree[*v] < k {
+                        removed[*v] = true;
+                        queue.push_back(*v);
+                    }
+                }
+            }
+        }
+        let survivors: HashSet<String> = indexed.ids.iter()
+            .zip(removed)
+            .filter(|(_id, removed)| !removed)
+            .map(|(id, _removed)| id.to_string())
+            .collect();
+        self.induced_subgraph(&survivors)
+    }
+
+
+
+
+    /// Betweenness centrality from Brandes' algorithm, treating edges as undirected and parallel edges as one:
+    /// for each node, the number of shortest paths between other pairs of nodes that pass through it, with paths
+    /// split evenly where several are shortest. With normalized the counts are divided by the number of pairs
+    /// excluding the node, (n - 1)(n - 2) / 2, giving scores from 0 to 1 that compare across graph sizes.
+    /// O(V * E), so best kept to graphs of moderate size 
+    pub fn betweenness_centrality(&self, normalized: bool) -> HashMap<String, f64> {
+        let indexed = Indexed::new(self);
+        let neighbors: Vec<Vec<usize>> = indexed.neighbor_sets().into_iter().map(|set| set.into_iter().collect()).collect();
+        let n = indexed.ids.len();
+        let mut centrality = vec![0.0; n];
+        for s in 0..n {
+            // BFS from s counting shortest paths, then accumulate dependencies in reverse BFS order 
+            let mut order = Vec::with_capacity(n);
+            let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+            let mut paths = vec![0.0; n];
+            let mut distance: Vec<Option<usize>> = vec![None; n];
+            paths[s] = 1.0;
+            distance[s] = Some(0);
+            let mut queue = VecDeque::from([s]);
+            while let Some(u) = queue.pop_front() {
+                order.push(u);
+                let next = distance[u].map(|d| d + 1);
+                for v in &neighbors[u] {
+                    if distance[*v].is_none() {
+                        distance[*v] = next;
+                        queue.push_back(*v);
+                    }
+                    if distance[*v] == next {
+                        paths[*v] += paths[u];
+                        predecessors[*v].push(u);
+                    }
+                }
+            }
+            let mut dependency = vec![0.0; n];
+            for w in order.into_iter().rev() {
+                for v in &predecessors[w] {
+                    dependency[*v] += paths[*v] / paths[w] * (1.0 + dependency[w]);
+                }
+                if w != s {
+                    centrality[w] += dependency[w];
+                }
+            }
+        }
+        // every undirected path was counted once from each end 
+        let pairs = if n > 2 { ((n - 1) * (n - 2)) as f64 / 2.0 } else { 1.0 };
+        let scale = if normalized { 0.5 / pairs } else { 0.5 };
+        indexed.ids.iter()
+            .zip(centrality)
+            .map(|(id, c)| (id.to_string(), c * scale))
+            .collect()
+    }
+
+
+    /// The number of triangles, i.e. unordered triples of mutually adjacent nodes, ignoring edge direction,
+    /// parallel edges and self-loops. The numerator of the global clustering coefficient 
+    pub fn triangle_count(&self) -> usize {
+        let neighbors = Indexed::new(self).neighbor_sets();
+        let mut count = 0;
+        // each triangle u < v < w is counted once, at its edge (u, v) 
+        for (u, adjacent) in neighbors.iter().enumerate() {
+            for v in adjacent.iter().filter(|v| **v > u) {
+                count += adjacent.intersection(&neighbors[*v]).filter(|w| **w > *v).count();
+            }
+        }
+        count
+    }
+
+
+    /// The breadth-first spanning tree of root_id's component, treating edges as undirected: the nodes reachable
+    /// from root_id, and for each of them other than the root only the edge that first reached it, so cross edges,
+    /// parallel edges and self-loops are dropped. Ties are broken by edge variant then id for a stable result.
+    /// Meta, layout_seed and the hyperedges among the reached nodes carry over, as in induced_subgraph.
+    /// Empty if root_id is not in the graph 
+    pub fn spanning_tree(&self, root_id: &str) -> Graph {
+        let indexed = Indexed::new(self);
+        let mut tree = Graph::new();
+        let Ok(root) = indexed.ids.binary_search(&root_id) else {
+            return tree;
+        };
+        let mut reached = vec![false; indexed.ids.len()];
+        reached[root] = true;
+        let mut tree_edges = Vec::new();
+        let mut queue = VecDeque::from([root]);
+        while let Some(u) = queue.pop_front() {
+            for (v, e) in &indexed.adjacency[u] {
+                if !reached[*v] {
+                    reached[*v] = true;
+                    tree_edges.push(*e);
+                    queue.push_back(*v);
+                }
+            }
+        }
+        for (variant, collection) in &self.nodes {
+            for (id, node) in collection {
+                if indexed.ids.binary_search(&id.as_str()).is_ok_and(|i| reached[i]) {
+                    tree.nodes.entry(variant.clone()).or_default().insert(id.clone(), node.clone());
+                }
+            }
+        }
+        for e in tree_edges {
+            let (variant, id) = (indexed.edge_variants[e], indexed.edges[e].2);
+            let edge = self.edges[variant][id].clone();
+            tree.edges.entry(variant.to_string()).or_default().insert(id.to_string(), edge);
+        }
+        self.carry_graph_state(&mut tree);
+        tree
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Graph, GraphError};
+    use super::super::test_support::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn bridge_between_two_triangles() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 6), (6, 4)]);
+        assert_eq!(graph.bridges(), vec![edge_id(3, 4)]);
+    }
+
+    #[test]
+    fn every_edge_of_a_tree_is_a_bridge_but_parallel_edges_are_not() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (2, 4)]);
+        assert_eq!(graph.bridges().len(), 3);
+        graph.source_edge_target(&thing(2), &thing(1), "link", ()).unwrap();
+        assert_eq!(graph.bridges(), vec![edge_id(2, 3), edge_id(2, 4)]);
+    }
+
+    #[test]
+    fn barbell_center_is_the_articulation_point() {
+        // triangles 1-2-3 and 5-6-7 both joined to node 4 
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 5), (5, 6), (6, 7), (7, 5)]);
+        assert_eq!(graph.articulation_points(), vec![id(3), id(4), id(5)]);
+        let barbell = graph_from_edges(&[(1, 2), (2, 4), (4, 1), (4, 5), (5, 6), (6, 4)]);
+        assert_eq!(barbell.articulation_points(), vec![id(4)]);
+    }
+
+    #[test]
+    fn clustering_of_triangle_and_star() {
+        let triangle = graph_from_edges(&[(1, 2), (2, 3), (3, 1)]);
+        assert!(triangle.clustering_coefficients().values().all(|c| *c == 1.0));
+        let star = graph_from_edges(&[(1, 2), (1, 3), (1, 4)]);
+        let coefficients = star.clustering_coefficients();
+        assert_eq!(coefficients[&id(1)], 0.0);
+        assert_eq!(coefficients[&id(2)], 0.0);
+        let mut almost = graph_from_edges(&[(1, 2), (1, 3), (1, 4), (2, 3)]);
+        assert_eq!(almost.clustering_coefficients()[&id(1)], 1.0 / 3.0);
+        assert_eq!(almost.neighbors(&id(1)), vec![id(2), id(3), id(4)]);
+        almost.remove_node(&id(4));
+        assert_eq!(almost.neighbors(&id(1)), vec![id(2), id(3)]);
+    }
+
+    #[test]
+    fn only_the_largest_component_survives() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (10, 11), (11, 12), (12, 13), (13, 14)]);
+        graph.add_node_from(&thing(20)).unwrap();
+        assert_eq!(graph.connected_components().len(), 3);
+        graph.retain_largest_component();
+        let ids: HashSet<&String> = graph.nodes["Thing"].keys().collect();
+        assert_eq!(ids, [id(10), id(11), id(12), id(13), id(14)].iter().collect());
+        assert_eq!(graph.edges["link"].len(), 4);
+
+        let mut tied = graph_from_edges(&[(5, 6), (1, 2)]);
+        tied.retain_largest_component();
+        assert!(tied.contains_node(&id(1)) && !tied.contains_node(&id(5)));
+    }
+
+    #[test]
+    fn label_propagation_separates_two_cliques() {
+        let mut edges = Vec::new();
+        for clique in [[1, 2, 3, 4], [5, 6, 7, 8]] {
+            for (i, a) in clique.iter().enumerate() {
+                for b in &clique[i + 1..] {
+                    edges.push((*a, *b));
+                }
+            }
+        }
+        edges.push((4, 5));
+        let graph = graph_from_edges(&edges);
+        let labels = graph.label_propagation(10);
+        assert!([2, 3, 4].iter().all(|pk| labels[&id(*pk)] == labels[&id(1)]));
+        assert!([6, 7, 8].iter().all(|pk| labels[&id(*pk)] == labels[&id(5)]));
+        assert_ne!(labels[&id(1)], labels[&id(5)]);
+        assert_eq!(labels, graph.label_propagation(10));
+    }
+
+    #[test]
+    fn two_triangles_split_into_two_graphs() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (4, 5), (5, 6), (6, 4)]);
+        for (pk, members) in [(1, vec![id(1), id(2)]), (2, vec![id(3), id(4)])] {
+            let hyperedge = crate::fd3d::HyperEdge{variant: "coauthored", variant_pk: pk, id: format!("coauthored|{}", pk), members, props: ()};
+            graph.add_hyperedge(&hyperedge).unwrap();
+        }
+        graph.meta.zoom_id = Some(id(1));
+        let parts = graph.split_components();
+        assert_eq!(parts.len(), 2);
+        for (part, first) in parts.iter().zip([1, 4]) {
+            assert_eq!(part.nodes["Thing"].len(), 3);
+            assert_eq!(part.edges["link"].len(), 3);
+            assert!(part.contains_node(&id(first)));
+            assert!(part.validate().is_ok());
+        }
+        let hyperedges: Vec<&String> = parts[0].hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|1"]);
+        assert!(parts[1].hyperedges.is_empty());
+        assert_eq!(parts[0].meta.zoom_id, Some(id(1)));
+        assert_eq!(parts[1].meta.zoom_id, None);
+    }
+
+    #[test]
+    fn degree_histogram_of_a_star() {
+        let mut graph = graph_from_edges(&[(1, 2), (1, 3), (1, 4), (5, 1)]);
+        graph.add_node_from(&thing(6)).unwrap();
+        let histogram = graph.degree_histogram();
+        assert_eq!(histogram.into_iter().collect::<Vec<_>>(), vec![(0, 1), (1, 4), (4, 1)]);
+    }
+
+    #[test]
+    fn pagerank_favours_the_sink() {
+        let graph = graph_from_edges(&[(1, 2), (2, 4), (3, 4), (1, 3)]);
+        let ranks = graph.pagerank(0.85, 50);
+        assert!((ranks.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        let highest = ranks.iter().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+        assert_eq!(highest.0, &id(4));
+        assert!(ranks[&id(1)] < ranks[&id(2)]);
+    }
+
+    #[test]
+    fn assert_acyclic_reports_a_cycle() {
+        let tree = graph_from_edges(&[(1, 2), (1, 3), (3, 4), (2, 4)]);
+        assert!(tree.assert_acyclic().is_ok());
+        let cyclic = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 2)]);
+        match cyclic.assert_acyclic() {
+            Err(GraphError::Cycle{nodes}) => assert_eq!(nodes, vec![id(2), id(3), id(4)]),
+            _ => panic!("expected a cycle"),
+        }
+        assert_eq!(graph_from_edges(&[(5, 5)]).find_cycle(), Some(vec![id(5)]));
+    }
+
+    #[test]
+    fn all_paths_finds_both_routes() {
+        // a square 1-2-4 and 1-3-4 plus a long detour 1-5-6-7-4 
+        let graph = graph_from_edges(&[(1, 2), (2, 4), (1, 3), (4, 3), (1, 5), (5, 6), (6, 7), (7, 4)]);
+        let paths = graph.all_paths(&id(1), &id(4), 2);
+        assert_eq!(paths, vec![vec![id(1), id(2), id(4)], vec![id(1), id(3), id(4)]]);
+        assert_eq!(graph.all_paths(&id(1), &id(4), 4).len(), 3);
+        assert!(graph.all_paths(&id(1), &id(9), 4).is_empty());
+    }
+
+
+    #[test]
+    fn would_create_cycle_checks_reachability() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (4, 3)]);
+        assert!(graph.would_create_cycle(&id(3), &id(1)));
+        assert!(!graph.would_create_cycle(&id(1), &id(3)));
+        assert!(graph.would_create_cycle(&id(3), &id(4)));
+        assert!(!graph.would_create_cycle(&id(4), &id(2)));
+        assert!(graph.would_create_cycle(&id(2), &id(2)));
+        assert!(!graph.would_create_cycle(&id(1), "Thing|99"));
+    }
+
+
+    #[test]
+    fn diameter_of_a_path_and_disconnected_graphs() {
+        assert_eq!(graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 5)]).diameter(), Some(4));
+        assert_eq!(graph_from_edges(&[(1, 2), (2, 3), (3, 1)]).diameter(), Some(1));
+        assert_eq!(graph_from_edges(&[(1, 2), (3, 4)]).diameter(), None);
+        assert_eq!(Graph::new().diameter(), None);
+    }
+
+
+    #[test]
+    fn bipartite_sides_two_colour_the_graph() {
+        let graph = graph_from_edges(&[(1, 10), (2, 10), (2, 11), (3, 11), (4, 6)]);
+        let (first, second) = graph.bipartite_sides().unwrap();
+        let set = |pks: &[i32]| pks.iter().map(|pk| id(*pk)).collect::<HashSet<String>>();
+        assert_eq!(first, set(&[1, 2, 3, 4]));
+        assert_eq!(second, set(&[6, 10, 11]));
+        assert_eq!(graph_from_edges(&[(1, 2), (2, 3), (3, 1)]).bipartite_sides(), None);
+        assert_eq!(graph_from_edges(&[(1, 1)]).bipartite_sides(), None);
+    }
+
+
+    #[test]
+    fn trimming_leaves_peels_path_ends() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 5)]);
+        assert_eq!(graph.leaf_nodes(), vec![id(1), id(5)]);
+        graph.trim_leaves(1);
+        assert!(!graph.contains_node(&id(1)) && !graph.contains_node(&id(5)));
+        assert_eq!(graph.nodes["Thing"].len(), 3);
+        assert_eq!(graph.leaf_nodes(), vec![id(2), id(4)]);
+        graph.trim_leaves(5);
+        assert_eq!(graph.nodes["Thing"].len(), 1);
+        assert!(graph_from_edges(&[(1, 2), (2, 3), (3, 1)]).leaf_nodes().is_empty());
+    }
+
+
+    #[test]
+    fn two_core_drops_pendant_chains() {
+        // a square with a diagonal, plus chains hanging off two corners 
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 1), (1, 3), (3, 5), (5, 6), (2, 7)]);
+        let core = graph.k_core(2);
+        let mut ids: Vec<&String> = core.nodes["Thing"].keys().collect();
+        ids.sort();
+        assert_eq!(ids, vec![&id(1), &id(2), &id(3), &id(4)]);
+        assert_eq!(core.edges["link"].len(), 5);
+        assert!(graph.k_core(3).nodes.is_empty());
+        assert_eq!(graph.k_core(0).nodes["Thing"].len(), 7);
+
+        let mut graph = graph;
+        graph.layout_seed = Some(9);
+        graph.meta.zoom_id = Some(id(1));
+        for (pk, members) in [(1, vec![id(1), id(3)]), (2, vec![id(3), id(5)])] {
+            let hyperedge = crate::fd3d::HyperEdge{variant: "coauthored", variant_pk: pk, id: format!("coauthored|{}", pk), members, props: ()};
+            graph.add_hyperedge(&hyperedge).unwrap();
+        }
+        let core = graph.k_core(2);
+        assert_eq!(core.layout_seed, Some(9));
+        assert_eq!(core.meta.zoom_id, Some(id(1)));
+        let hyperedges: Vec<&String> = core.hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|1"]);
+    }
+
+    #[test]
+    fn betweenness_peaks_in_the_middle_of_a_path() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 5)]);
+        let raw = graph.betweenness_centrality(false);
+        assert_eq!(raw[&id(1)], 0.0);
+        assert_eq!(raw[&id(2)], 3.0);
+        assert_eq!(raw[&id(3)], 4.0);
+        let normalized = graph.betweenness_centrality(true);
+        let top = normalized.iter().max_by(|a, b| a.1.total_cmp(b.1)).unwrap();
+        assert_eq!(top.0, &id(3));
+        assert!((normalized[&id(3)] - 4.0 / 6.0).abs() < 1e-12);
+        // in a square with one diagonal the two ends of the diagonal split the paths between the other two 
+        let square = graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 1), (1, 3)]).betweenness_centrality(false);
+        assert_eq!(square[&id(1)], 0.5);
+        assert_eq!(square[&id(2)], 0.0);
+    }
+
+    #[test]
+    fn triangles_of_a_triangle_and_a_four_clique() {
+        assert_eq!(graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4)]).triangle_count(), 1);
+        let k4 = graph_from_edges(&[(1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4), (4, 3), (4, 4)]);
+        assert_eq!(k4.triangle_count(), 4);
+        assert_eq!(graph_from_edges(&[(1, 2), (2, 3), (3, 4), (4, 1)]).triangle_count(), 0);
+    }
+
+    #[test]
+    fn spanning_tree_keeps_one_edge_per_reached_node() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4), (4, 1), (4, 4), (2, 1), (5, 6)]);
+        graph.source_edge_target(&thing(4), &thing(2), "cites", ()).unwrap();
+        let tree = graph.spanning_tree(&id(1));
+        let nodes = tree.nodes["Thing"].len();
+        assert_eq!(nodes, 4);
+        assert!(!tree.nodes["Thing"].contains_key(&id(5)));
+        let edges: usize = tree.edges.values().map(|collection| collection.len()).sum();
+        assert_eq!(edges, nodes - 1);
+        assert_eq!(tree.split_components().len(), 1);
+        assert!(graph.spanning_tree("nope").nodes.is_empty());
+
+        graph.layout_seed = Some(4);
+        graph.set_variant_style("Thing", serde_json::json!({"color": "#00ff00"}));
+        for (pk, members) in [(1, vec![id(1), id(4)]), (2, vec![id(4), id(5)])] {
+            let hyperedge = crate::fd3d::HyperEdge{variant: "coauthored", variant_pk: pk, id: format!("coauthored|{}", pk), members, props: ()};
+            graph.add_hyperedge(&hyperedge).unwrap();
+        }
+        let tree = graph.spanning_tree(&id(1));
+        assert_eq!(tree.layout_seed, Some(4));
+        assert_eq!(tree.meta, graph.meta);
+        let hyperedges: Vec<&String> = tree.hyperedges["coauthored"].keys().collect();
+        assert_eq!(hyperedges, ["coauthored|1"]);
+    }
+}
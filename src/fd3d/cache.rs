@@ -0,0 +1,188 @@
+//! An opt-in cache of each node's and edge's encoded JSON, so reserializing a mostly unchanged graph only encodes what changed 
+
+use std::collections::HashMap;
+use super::Graph;
+
+
+/// variant -> id -> the encoded JSON of the value. An entry missing here is dirty and is encoded on the next call 
+type Fragments = HashMap<String, HashMap<String, String>>;
+
+
+/// The encoded nodes and edges of a graph created with Graph::with_cache 
+#[derive(Default)]
+pub(super) struct FragmentCache {
+    nodes: Fragments,
+    edges: Fragments,
+}
+
+
+/// append collections to out as a JSON object, reusing the fragment of every value that has one and encoding the rest.
+/// Afterwards fragments holds exactly the current values, so removed nodes and edges do not linger 
+fn write_collections<E>(out: &mut String, collections: &HashMap<String, HashMap<String, serde_json::Value>>, fragments: &mut Fragments, encode: &mut E) -> Result<(), serde_json::Error> where 
+    E: FnMut(&serde_json::Value) -> Result<String, serde_json::Error>
+{
+    let mut fresh = Fragments::with_capacity(fragments.len());
+    out.push('{');
+    for (i, (variant, collection)) in collections.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&serde_json::to_string(variant)?);
+        out.push_str(":{");
+        let mut cached = fragments.remove(variant).unwrap_or_default();
+        let mut written = HashMap::with_capacity(collection.len());
+        for (j, (id, value)) in collection.iter().enumerate() {
+            if j > 0 {
+                out.push(',');
+            }
+            let fragment = match cached.remove(id) {
+                Some(json) => json,
+                None => encode(value)?,
+            };
+            out.push_str(&serde_json::to_string(id)?);
+            out.push(':');
+            out.push_str(&fragment);
+            written.insert(id.clone(), fragment);
+        }
+        fresh.insert(variant.clone(), written);
+        out.push('}');
+    }
+    out.push('}');
+    *fragments = fresh;
+    Ok(())
+}
+
+
+impl Graph {
+    /// Return a new empty graph that caches the encoded JSON of each node and edge, see Graph::to_cached_json_string 
+    pub fn with_cache() -> Self {
+        let mut graph = Graph::new();
+        graph.cache = Some(FragmentCache::default());
+        graph
+    }
+
+
+    /// The graph as compact JSON, the same document serde_json::to_string produces, but reusing the cached encoding of
+    /// every node and edge that is unchanged since the last call, so after a small edit to a large graph only the edited
+    /// entries are encoded again. Graph's own methods mark what they change as dirty; code that edits the pub nodes
+    /// or edges maps directly must call invalidate_cache afterwards, or stale JSON is emitted for the edited entries.
+    /// A graph that was not created with_cache simply encodes everything 
+    pub fn to_cached_json_string(&mut self) -> Result<String, serde_json::Error> {
+        self.cached_json_string_with(&mut |value| serde_json::to_string(value))
+    }
+
+
+    /// to_cached_json_string with the encoder of each dirty node and edge passed in, so the tests can watch it 
+    fn cached_json_string_with<E>(&mut self, encode: &mut E) -> Result<String, serde_json::Error> where 
+        E: FnMut(&serde_json::Value) -> Result<String, serde_json::Error>
+    {
+        let Some(cache) = self.cache.as_mut() else {
+            return serde_json::to_string(self);
+        };
+        let mut out = String::from("{\"nodes\":");
+        write_collections(&mut out, &self.nodes, &mut cache.nodes, encode)?;
+        out.push_str(",\"edges\":");
+        write_collections(&mut out, &self.edges, &mut cache.edges, encode)?;
+        if !self.hyperedges.is_empty() {
+            out.push_str(",\"hyperedges\":");
+            out.push_str(&serde_json::to_string(&self.hyperedges)?);
+        }
+        out.push_str(",\"meta\":");
+        out.push_str(&serde_json::to_string(&self.meta)?);
+        if let Some(seed) = self.layout_seed {
+            out.push_str(",\"layout_seed\":");
+            out.push_str(&seed.to_string());
+        }
+        #[cfg(feature = "time")]
+        {
+            out.push_str(",\"generated_at\":");
+            out.push_str(&self.generated_at.to_string());
+        }
+        out.push('}');
+        Ok(out)
+    }
+
+
+    /// Mark every node and edge as changed, so the next to_cached_json_string encodes them all.
+    /// Needed after editing the pub nodes or edges maps directly; does nothing for a graph without a cache 
+    pub fn invalidate_cache(&mut self) {
+        if let Some(cache) = self.cache.as_mut() {
+            cache.nodes.clear();
+            cache.edges.clear();
+        }
+    }
+
+
+    /// mark the node id of variant as changed 
+    pub(super) fn touch_node(&mut self, variant: &str, id: &str) {
+        if let Some(fragments) = self.cache.as_mut().and_then(|cache| cache.nodes.get_mut(variant)) {
+            fragments.remove(id);
+        }
+    }
+
+
+    /// mark the edge id of variant as changed 
+    pub(super) fn touch_edge(&mut self, variant: &str, id: &str) {
+        if let Some(fragments) = self.cache.as_mut().and_then(|cache| cache.edges.get_mut(variant)) {
+            fragments.remove(id);
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_support::*;
+    use serde::{Serialize, Serializer};
+    use std::cell::Cell;
+
+    /// a value that counts how often it is serialized 
+    struct Counted<'a>(&'a serde_json::Value, &'a Cell<usize>);
+
+    impl Serialize for Counted<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.1.set(self.1.get() + 1);
+            self.0.serialize(serializer)
+        }
+    }
+
+    /// to_cached_json_string, also returning how many nodes and edges were serialized 
+    fn cached_json(graph: &mut Graph) -> (String, usize) {
+        let count = Cell::new(0);
+        let json = graph.cached_json_string_with(&mut |value| serde_json::to_string(&Counted(value, &count))).unwrap();
+        (json, count.get())
+    }
+
+    #[test]
+    fn cached_json_only_encodes_changes() {
+        let mut graph = Graph::with_cache();
+        link(&mut graph, 1, 2);
+        link(&mut graph, 2, 3);
+        let (first, serialized) = cached_json(&mut graph);
+        assert_eq!(serialized, 5);
+        let parsed: serde_json::Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(parsed, serde_json::to_value(&graph).unwrap());
+
+        graph.add_node_from(&thing(4)).unwrap();
+        let (second, serialized) = cached_json(&mut graph);
+        assert_eq!(serialized, 1);
+        let parsed: serde_json::Value = serde_json::from_str(&second).unwrap();
+        assert_eq!(parsed, serde_json::to_value(&graph).unwrap());
+
+        graph.set_edge_directed("link", &edge_id(1, 2), true).unwrap();
+        graph.remove_node(&id(4));
+        let (third, serialized) = cached_json(&mut graph);
+        assert_eq!(serialized, 1);
+        assert!(third.contains("\"directed\":true") && !third.contains(&id(4)));
+
+        // edits straight through the pub maps need invalidate_cache 
+        graph.nodes.get_mut("Thing").unwrap().get_mut(&id(1)).unwrap()["name"] = "renamed".into();
+        assert!(!cached_json(&mut graph).0.contains("renamed"));
+        graph.invalidate_cache();
+        let (fourth, serialized) = cached_json(&mut graph);
+        assert_eq!(serialized, 5);
+        assert!(fourth.contains("renamed"));
+        assert_eq!(cached_json(&mut graph).1, 0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Building one Graph from many threads without sharing it: workers send what they produce over a channel
+//! and the builder assembles the Graph at the end 
+
+use std::{fmt, sync::mpsc};
+use serde::Serialize;
+use super::{Graph, GraphError, ToNode, ToNodeJSON};
+
+
+/// what a GraphSender sends: nodes are serialized on the worker thread, edges arrive as a small graph
+/// holding the edge and its two nodes 
+enum Item {
+    Node { variant: String, id: String, json: serde_json::Value },
+    Fragment(Box<Graph>),
+}
+
+
+/// Collects nodes and edges sent from any number of threads through GraphSenders, and assembles them into one
+/// Graph in finish. Nothing is locked: each worker serializes its own items and only the channel is shared.
+/// When the same node or edge id is sent more than once, whichever arrives last wins 
+pub struct ConcurrentGraphBuilder {
+    sender: mpsc::Sender<Item>,
+    receiver: mpsc::Receiver<Item>,
+}
+
+
+/// A cloneable handle for sending nodes and edges to a ConcurrentGraphBuilder from a worker thread 
+#[derive(Clone)]
+pub struct GraphSender {
+    sender: mpsc::Sender<Item>,
+}
+
+
+impl Default for ConcurrentGraphBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl ConcurrentGraphBuilder {
+    /// return a new builder with nothing sent yet 
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        ConcurrentGraphBuilder{sender, receiver}
+    }
+
+    /// A handle to move into a worker thread; clone it for as many workers as needed 
+    pub fn sender(&self) -> GraphSender {
+        GraphSender{sender: self.sender.clone()}
+    }
+
+    /// Assemble the Graph from everything sent. This blocks until every GraphSender has been dropped,
+    /// so drop (or join the threads holding) them first 
+    pub fn finish(self) -> Graph {
+        let ConcurrentGraphBuilder{sender, receiver} = self;
+        drop(sender);
+        let mut graph = Graph::new();
+        for item in receiver {
+            match item {
+                Item::Node{variant, id, json} => {
+                    graph.nodes.entry(variant).or_default().insert(id, json);
+                },
+                Item::Fragment(fragment) => graph.merge(*fragment),
+            }
+        }
+        graph
+    }
+}
+
+
+impl GraphSender {
+    /// Serialize n on this thread and send it to the builder, as Graph::add_node_from would add it.
+    /// Fails if the node cannot be serialized or the builder was dropped without finishing 
+    pub fn send_node<NV, PK, T>(&self, n: &dyn ToNodeJSON<NV, PK, T>) -> Result<(), GraphError> where 
+        NV: Serialize + fmt::Display,
+        PK: Serialize + fmt::Debug, 
+        T:  Serialize  
+    {
+        let node = n.to_node();
+        let json = node.to_node_json()?;
+        self.send(Item::Node{variant: node.variant.to_string(), id: node.id, json})
+    }
+
+    /// Send the source and target nodes with an edge between them, as Graph::source_edge_target would add them.
+    /// Fails if anything cannot be serialized or the builder was dropped without finishing 
+    pub fn source_edge_target<NVS, PKS, TS, EV, ET, NVT, PKT, TT>(&self, n_source: &dyn ToNode<NVS, PKS, TS>, n_target: &dyn ToNode<NVT, PKT, TT>, edge_variant: EV, edge_props: ET) -> Result<(), GraphError> where 
+        NVS: Serialize + fmt::Display,
+        PKS: Serialize + fmt::Debug,
+        TS:  Serialize,
+        EV: Serialize + fmt::Display,
+        ET:  Serialize,
+        NVT: Serialize + fmt::Display,
+        PKT: Serialize + fmt::Debug,
+        TT:  Serialize,
+    {
+        let mut fragment = Graph::new();
+        fragment.source_edge_target(n_source, n_target, edge_variant, edge_props)?;
+        self.send(Item::Fragment(Box::new(fragment)))
+    }
+
+    fn send(&self, item: Item) -> Result<(), GraphError> {
+        self.sender.send(item).map_err(|_closed| GraphError::BuilderDropped)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_support::*;
+
+    #[test]
+    fn items_from_every_thread_are_collected() {
+        let builder = ConcurrentGraphBuilder::new();
+        std::thread::scope(|scope| {
+            for worker in 0..2 {
+                let sender = builder.sender();
+                scope.spawn(move || {
+                    for pk in 0..50 {
+                        sender.send_node(&thing(worker * 100 + pk)).unwrap();
+                    }
+                    sender.source_edge_target(&thing(worker * 100), &thing(worker * 100 + 1), "link", ()).unwrap();
+                });
+            }
+        });
+        let graph = builder.finish();
+        assert_eq!(graph.nodes["Thing"].len(), 100);
+        assert_eq!(graph.edges["link"].len(), 2);
+        assert!(graph.edges["link"].contains_key(&edge_id(100, 101)));
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn sending_to_a_dropped_builder_fails() {
+        let builder = ConcurrentGraphBuilder::new();
+        let sender = builder.sender();
+        drop(builder);
+        assert!(matches!(sender.send_node(&thing(1)), Err(GraphError::BuilderDropped)));
+    }
+}
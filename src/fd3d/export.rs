@@ -0,0 +1,733 @@
+//! Methods for writing a Graph out in formats other than the single JSON document Graph serializes to 
+
+use std::{collections::{BTreeMap, BTreeSet, HashMap}, io::{self, Write}};
+use serde::{Serialize, Deserialize};
+use super::{Graph, GraphError, GraphMeta, edge_ends};
+
+
+/// write each value in the collection as one line of JSON, sorted by id so the output is reproducible 
+fn write_ndjson<W: Write>(mut writer: W, collection: &HashMap<String, serde_json::Value>) -> io::Result<()> {
+    let mut ids: Vec<&String> = collection.keys().collect();
+    ids.sort();
+    for id in ids {
+        serde_json::to_writer(&mut writer, &collection[id])?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+
+/// call write_ndjson for each collection in variant order, obtaining the writer for that variant from open 
+fn write_by_variant<W: Write>(collections: &HashMap<String, HashMap<String, serde_json::Value>>, mut open: impl FnMut(&str) -> W) -> io::Result<()> {
+    let mut variants: Vec<&String> = collections.keys().collect();
+    variants.sort();
+    for variant in variants {
+        write_ndjson(open(variant), &collections[variant])?;
+    }
+    Ok(())
+}
+
+
+/// A Graph in which every node id has been replaced by a u32 index into a separate id table,
+/// which makes the JSON for big graphs considerably smaller. See Graph::to_compact and Graph::from_compact 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CompactGraph {
+    /// node variant -> nodes, each with its "id" replaced by an index 
+    pub nodes: HashMap<String, Vec<serde_json::Value>>,
+    /// edge variant -> edges, each with its "source" and "target" replaced by indices. Edge ids are unchanged 
+    pub edges: HashMap<String, Vec<serde_json::Value>>,
+    /// hyperedge variant -> hyperedges, each with its "members" replaced by indices. Hyperedge ids are unchanged 
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub hyperedges: HashMap<String, Vec<serde_json::Value>>,
+    #[serde(default)]
+    pub meta: GraphMeta,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layout_seed: Option<u64>,
+}
+
+
+/// The variants present in a Graph and the prop keys observed on each, without the data itself,
+/// e.g. for generating a legend or filter UI. See Graph::schema 
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct GraphSchema {
+    /// node variant -> the union of the prop keys of its nodes 
+    pub nodes: BTreeMap<String, BTreeSet<String>>,
+    /// edge variant -> the union of the prop keys of its edges 
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+
+/// the union of the keys of the "props" objects in collection 
+fn prop_keys(collection: &HashMap<String, serde_json::Value>) -> BTreeSet<String> {
+    collection.values()
+        .filter_map(|value| value["props"].as_object())
+        .flat_map(|props| props.keys().cloned())
+        .collect()
+}
+
+
+/// every (variant, id, value) in collections, sorted by variant then id so exports are reproducible 
+fn sorted_entries(collections: &HashMap<String, HashMap<String, serde_json::Value>>) -> Vec<(&str, &str, &serde_json::Value)> {
+    let mut entries: Vec<(&str, &str, &serde_json::Value)> = collections.iter()
+        .flat_map(|(variant, collection)| collection.iter().map(move |(id, value)| (variant.as_str(), id.as_str(), value)))
+        .collect();
+    entries.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    entries
+}
+
+
+/// approximately how many bytes value takes as compact JSON: exact except that escaped characters in strings
+/// are counted as one byte 
+fn estimate_value_bytes(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Null => 4,
+        serde_json::Value::Bool(b) => if *b { 4 } else { 5 },
+        serde_json::Value::Number(n) => n.to_string().len(),
+        serde_json::Value::String(s) => s.len() + 2,
+        serde_json::Value::Array(items) => 2 + items.len().saturating_sub(1) + items.iter().map(estimate_value_bytes).sum::<usize>(),
+        serde_json::Value::Object(map) => 2 + map.len().saturating_sub(1) + map.iter().map(|(key, value)| key.len() + 3 + estimate_value_bytes(value)).sum::<usize>(),
+    }
+}
+
+
+/// estimate_value_bytes for the variant -> id -> value maps of a Graph 
+fn estimate_collections_bytes(collections: &HashMap<String, HashMap<String, serde_json::Value>>) -> usize {
+    // braces, a quoted key, a colon and a comma for every entry of an object 
+    let object = |keys: usize, key_bytes: usize, value_bytes: usize| 2 + keys.saturating_sub(1) + 3 * keys + key_bytes + value_bytes;
+    let collection_bytes: usize = collections.values()
+        .map(|collection| object(collection.len(), collection.keys().map(String::len).sum(), collection.values().map(estimate_value_bytes).sum()))
+        .sum();
+    object(collections.len(), collections.keys().map(String::len).sum(), collection_bytes)
+}
+
+
+/// an io::Write that only counts the bytes written to it 
+struct ByteCounter(usize);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+/// escape a key for use as one reference token of an RFC 6901 JSON Pointer 
+fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+
+/// append to patch the RFC 6902 operations turning from into to, both found at path. Objects are diffed key by key
+/// (in sorted order), anything else that differs is replaced whole 
+fn diff_values(path: &str, from: &serde_json::Value, to: &serde_json::Value, patch: &mut Vec<serde_json::Value>) {
+    match (from, to) {
+        (serde_json::Value::Object(from), serde_json::Value::Object(to)) => {
+            let keys: BTreeSet<&String> = from.keys().chain(to.keys()).collect();
+            for key in keys {
+                let child = format!("{}/{}", path, pointer_token(key));
+                match (from.get(key), to.get(key)) {
+                    (Some(from), Some(to)) => diff_values(&child, from, to, patch),
+                    (Some(_from), None) => patch.push(serde_json::json!({"op": "remove", "path": child})),
+                    (None, Some(to)) => patch.push(serde_json::json!({"op": "add", "path": child, "value": to})),
+                    (None, None) => {},
+                }
+            }
+        },
+        (from, to) if from != to => patch.push(serde_json::json!({"op": "replace", "path": path, "value": to})),
+        _ => {},
+    }
+}
+
+
+/// escape text for use in HTML element content or attribute values 
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+
+impl Graph {
+    /// Write the nodes as newline-delimited JSON, one writer per node variant.
+    /// open is called once for each variant to obtain the writer that variant's nodes are written to,
+    /// so the caller decides whether that is a file, a socket, a buffer etc. 
+    pub fn write_nodes_by_variant<W: Write>(&self, open: impl FnMut(&str) -> W) -> io::Result<()> {
+        write_by_variant(&self.nodes, open)
+    }
+
+    /// Write the edges as newline-delimited JSON, one writer per edge variant. See write_nodes_by_variant 
+    pub fn write_edges_by_variant<W: Write>(&self, open: impl FnMut(&str) -> W) -> io::Result<()> {
+        write_by_variant(&self.edges, open)
+    }
+
+
+    /// Replace every node id (in nodes, edge endpoints and hyperedge members) with a u32 index, returning the compacted
+    /// graph and the index -> id table. Node ids are numbered in sorted order; ids only referenced by dangling edges
+    /// or hyperedge members are numbered after those. Edge ends and members that are not strings are left as they are.
+    /// Fails with NotAnObject if a node, edge or hyperedge is not a JSON object, since each stores its id inside itself 
+    pub fn to_compact(&self) -> Result<(CompactGraph, Vec<String>), GraphError> {
+        let mut ids: Vec<String> = self.nodes.values().flat_map(|collection| collection.keys().cloned()).collect();
+        ids.sort();
+        let mut index: HashMap<String, u32> = ids.iter().enumerate().map(|(i, id)| (id.clone(), i as u32)).collect();
+        let mut index_of = |id: &str| -> u32 {
+            if let Some(i) = index.get(id) {
+                return *i;
+            }
+            let i = ids.len() as u32;
+            ids.push(id.to_string());
+            index.insert(id.to_string(), i);
+            i
+        };
+        let mut compact = CompactGraph{nodes: HashMap::new(), edges: HashMap::new(), hyperedges: HashMap::new(), meta: self.meta.clone(), layout_seed: self.layout_seed};
+        for (variant, collection) in &self.nodes {
+            let mut nodes: Vec<(u32, serde_json::Value)> = collection.iter()
+                .map(|(id, node)| {
+                    let mut node = node.clone();
+                    let i = index_of(id);
+                    node.as_object_mut().ok_or_else(|| GraphError::NotAnObject(id.clone()))?.insert("id".to_string(), i.into());
+                    Ok((i, node))
+                })
+                .collect::<Result<_, GraphError>>()?;
+            nodes.sort_by_key(|(i, _node)| *i);
+            compact.nodes.insert(variant.clone(), nodes.into_iter().map(|(_i, node)| node).collect());
+        }
+        for (variant, collection) in &self.edges {
+            let mut edge_ids: Vec<&String> = collection.keys().collect();
+            edge_ids.sort();
+            let edges = edge_ids.into_iter()
+                .map(|id| {
+                    let mut edge = collection[id].clone();
+                    let fields = edge.as_object_mut().ok_or_else(|| GraphError::NotAnObject(id.clone()))?;
+                    for end in ["source", "target"] {
+                        if let Some(value) = fields.get_mut(end) {
+                            if let Some(end_id) = value.as_str() {
+                                *value = index_of(end_id).into();
+                            }
+                        }
+                    }
+                    fields.insert("id".to_string(), id.as_str().into());
+                    Ok(edge)
+                })
+                .collect::<Result<_, GraphError>>()?;
+            compact.edges.insert(variant.clone(), edges);
+        }
+        for (variant, collection) in &self.hyperedges {
+            let mut hyperedge_ids: Vec<&String> = collection.keys().collect();
+            hyperedge_ids.sort();
+            let hyperedges = hyperedge_ids.into_iter()
+                .map(|id| {
+                    let mut hyperedge = collection[id].clone();
+                    let fields = hyperedge.as_object_mut().ok_or_else(|| GraphError::NotAnObject(id.clone()))?;
+                    if let Some(members) = fields.get_mut("members").and_then(|members| members.as_array_mut()) {
+                        for member in members.iter_mut() {
+                            if let Some(member_id) = member.as_str() {
+                                *member = index_of(member_id).into();
+                            }
+                        }
+                    }
+                    fields.insert("id".to_string(), id.as_str().into());
+                    Ok(hyperedge)
+                })
+                .collect::<Result<_, GraphError>>()?;
+            compact.hyperedges.insert(variant.clone(), hyperedges);
+        }
+        Ok((compact, ids))
+    }
+
+    /// Rehydrate a graph produced by to_compact, given the id table it returned.
+    /// Null edge ends and members are left as they are.
+    /// Fails with MissingCompactId for an edge or hyperedge without a string id, which it would otherwise be keyed by 
+    pub fn from_compact(compact: CompactGraph, ids: &[String]) -> Result<Graph, GraphError> {
+        let lookup = |value: &serde_json::Value| -> Result<String, GraphError> {
+            let i = value.as_u64().ok_or_else(|| GraphError::InvalidCompactIndex(value.clone()))?;
+            ids.get(i as usize).cloned().ok_or(GraphError::UnknownIndex(i))
+        };
+        let mut graph = Graph::new();
+        graph.meta = compact.meta;
+        graph.layout_seed = compact.layout_seed;
+        for (variant, nodes) in compact.nodes {
+            let collection = graph.nodes.entry(variant).or_default();
+            for mut node in nodes {
+                let id = lookup(&node["id"])?;
+                node["id"] = id.clone().into();
+                collection.insert(id, node);
+            }
+        }
+        for (variant, edges) in compact.edges {
+            let collection = graph.edges.entry(variant).or_default();
+            for mut edge in edges {
+                let Some(id) = edge.get("id").and_then(|id| id.as_str()).map(str::to_string) else {
+                    return Err(GraphError::MissingCompactId(edge));
+                };
+                for end in ["source", "target"] {
+                    if let Some(value) = edge.get_mut(end).filter(|value| !value.is_null()) {
+                        *value = lookup(value)?.into();
+                    }
+                }
+                collection.insert(id, edge);
+            }
+        }
+        for (variant, hyperedges) in compact.hyperedges {
+            let collection = graph.hyperedges.entry(variant).or_default();
+            for mut hyperedge in hyperedges {
+                let Some(id) = hyperedge.get("id").and_then(|id| id.as_str()).map(str::to_string) else {
+                    return Err(GraphError::MissingCompactId(hyperedge));
+                };
+                if let Some(members) = hyperedge.get_mut("members").and_then(|members| members.as_array_mut()) {
+                    for member in members.iter_mut().filter(|member| !member.is_null()) {
+                        *member = lookup(member)?.into();
+                    }
+                }
+                collection.insert(id, hyperedge);
+            }
+        }
+        Ok(graph)
+    }
+
+    /// The schema of the graph: each node and edge variant with the union of the prop keys seen on its instances 
+    pub fn schema(&self) -> GraphSchema {
+        GraphSchema{
+            nodes: self.nodes.iter().map(|(variant, collection)| (variant.clone(), prop_keys(collection))).collect(),
+            edges: self.edges.iter().map(|(variant, collection)| (variant.clone(), prop_keys(collection))).collect(),
+        }
+    }
+
+    /// The graph in the element format of Cytoscape.js:
+    /// `{"elements": {"nodes": [{"data": {"id", ..}}], "edges": [{"data": {"id", "source", "target", ..}}]}}`.
+    /// Each element's data holds its props alongside its id, variant and name (for nodes) or source and target (for edges).
+    /// A prop sharing a name with one of those keys is shadowed by it 
+    pub fn to_cytoscape(&self) -> serde_json::Value {
+        let element = |variant: &str, id: &str, value: &serde_json::Value, keys: &[&str]| {
+            let mut data = match &value["props"] {
+                serde_json::Value::Object(props) => props.clone(),
+                _ => serde_json::Map::new(),
+            };
+            data.insert("id".to_string(), id.into());
+            data.insert("variant".to_string(), variant.into());
+            for key in keys {
+                data.insert(key.to_string(), value[*key].clone());
+            }
+            serde_json::json!({"data": data})
+        };
+        let nodes: Vec<serde_json::Value> = sorted_entries(&self.nodes).into_iter()
+            .map(|(variant, id, node)| element(variant, id, node, &["name"]))
+            .collect();
+        let edges: Vec<serde_json::Value> = sorted_entries(&self.edges).into_iter()
+            .map(|(variant, id, edge)| element(variant, id, edge, &["source", "target"]))
+            .collect();
+        serde_json::json!({"elements": {"nodes": nodes, "edges": edges}})
+    }
+
+    /// The graph in JSON Graph Format (jsongraph.org): `{"graph": {"nodes": {id: {..}}, "edges": [{..}]}}`.
+    /// Each node has its name as label and `{"variant", "props"}` as metadata; each edge has its id, source, target,
+    /// its variant as relation, and its props as metadata. Edges are sorted by variant then id, and an edge's
+    /// directed flag is carried over when it is set 
+    pub fn to_json_graph_format(&self) -> serde_json::Value {
+        let nodes: serde_json::Map<String, serde_json::Value> = sorted_entries(&self.nodes).into_iter()
+            .map(|(variant, id, node)| {
+                let metadata = serde_json::json!({"variant": variant, "props": node["props"]});
+                (id.to_string(), serde_json::json!({"label": node["name"], "metadata": metadata}))
+            })
+            .collect();
+        let edges: Vec<serde_json::Value> = sorted_entries(&self.edges).into_iter()
+            .map(|(variant, id, edge)| {
+                let (source, target) = edge_ends(edge);
+                let mut jgf = serde_json::json!({
+                    "id": id, "source": source, "target": target, "relation": variant,
+                    "metadata": {"props": edge["props"]},
+                });
+                if let Some(directed) = edge["directed"].as_bool() {
+                    jgf["directed"] = directed.into();
+                }
+                jgf
+            })
+            .collect();
+        serde_json::json!({"graph": {"nodes": nodes, "edges": edges}})
+    }
+
+    /// The graph shaped for 3d-force-graph's graphData(). With flatten, that is the `{"nodes": [..], "links": [..]}`
+    /// arrays it consumes natively, each element the full node or edge JSON (so links keep their id, source and target),
+    /// sorted by variant then id. Links to missing nodes are left out, since 3d-force-graph rejects them.
+    /// Without flatten it is the usual variant-keyed document, for frontends that do their own reshaping 
+    pub fn to_force_graph_json(&self, flatten: bool) -> serde_json::Value {
+        if !flatten {
+            return serde_json::to_value(self).expect("a Graph of JSON values always serializes");
+        }
+        let nodes: Vec<serde_json::Value> = sorted_entries(&self.nodes).into_iter()
+            .map(|(_variant, _id, node)| node.clone())
+            .collect();
+        let links: Vec<serde_json::Value> = sorted_entries(&self.edges).into_iter()
+            .filter(|(_variant, _id, edge)| {
+                let (source, target) = edge_ends(edge);
+                self.contains_node(source) && self.contains_node(target)
+            })
+            .map(|(_variant, _id, edge)| edge.clone())
+            .collect();
+        serde_json::json!({"nodes": nodes, "links": links})
+    }
+
+    /// An RFC 6902 JSON Patch (an array of add / remove / replace operations) that turns the JSON of this graph into
+    /// the JSON of other, so clients with a JSON Patch library can apply graph updates directly.
+    /// Nodes, edges and every other object are diffed key by key, while arrays and scalars that differ are replaced whole 
+    pub fn json_patch_to(&self, other: &Graph) -> serde_json::Value {
+        let from = serde_json::to_value(self).expect("a Graph of JSON values always serializes");
+        let to = serde_json::to_value(other).expect("a Graph of JSON values always serializes");
+        let mut patch = Vec::new();
+        diff_values("", &from, &to, &mut patch);
+        patch.into()
+    }
+
+    /// The graph as MessagePack, a more compact binary alternative to JSON, with structs encoded as maps
+    /// so the field names survive just as they do in the JSON 
+    #[cfg(feature = "msgpack")]
+    pub fn to_msgpack(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+
+    /// Decode a graph written by to_msgpack 
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Graph, rmp_serde::decode::Error> {
+        rmp_serde::from_slice(bytes)
+    }
+
+    /// A quick estimate of the length of the graph's compact JSON, e.g. to decide whether to paginate before sending it.
+    /// It adds up the JSON sizes of the nodes, edges, hyperedges and meta without encoding them, which is exact apart
+    /// from escaped characters in strings, plus a small allowance for the remaining fields. See json_byte_len for the exact figure 
+    pub fn estimated_json_bytes(&self) -> usize {
+        const OTHER_FIELDS: usize = 64;
+        let collections = estimate_collections_bytes(&self.nodes) + estimate_collections_bytes(&self.edges) + estimate_collections_bytes(&self.hyperedges);
+        let meta = serde_json::to_value(&self.meta).map_or(0, |meta| estimate_value_bytes(&meta));
+        collections + meta + OTHER_FIELDS
+    }
+
+    /// The exact length of the graph's compact JSON, found by encoding it into a counter rather than a buffer 
+    pub fn json_byte_len(&self) -> usize {
+        let mut counter = ByteCounter(0);
+        serde_json::to_writer(&mut counter, self).expect("a Graph of JSON values always serializes");
+        counter.0
+    }
+
+    /// The graph's topology only, with the same shape as the full JSON but every node reduced to
+    /// {id, name, variant} and every edge to {id, source, target, variant}, e.g. for a minimap 
+    pub fn to_json_minimal(&self) -> serde_json::Value {
+        let slim = |collections: &HashMap<String, HashMap<String, serde_json::Value>>, keys: &[&str]| -> serde_json::Value {
+            collections.iter()
+                .map(|(variant, collection)| {
+                    let collection: serde_json::Map<String, serde_json::Value> = collection.iter()
+                        .map(|(id, value)| {
+                            let slimmed: serde_json::Map<String, serde_json::Value> = keys.iter()
+                                .map(|key| (key.to_string(), value[*key].clone()))
+                                .collect();
+                            (id.clone(), slimmed.into())
+                        })
+                        .collect();
+                    (variant.clone(), collection.into())
+                })
+                .collect::<serde_json::Map<String, serde_json::Value>>()
+                .into()
+        };
+        serde_json::json!({
+            "nodes": slim(&self.nodes, &["id", "name", "variant"]),
+            "edges": slim(&self.edges, &["id", "source", "target", "variant"]),
+            "meta": self.meta,
+        })
+    }
+
+    /// The graph as pretty-printed JSON, handy for debugging payloads in logs 
+    pub fn to_pretty_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// The graph as pretty-printed JSON, indenting each level by indent spaces 
+    pub fn to_pretty_json_string_with_indent(&self, indent: usize) -> Result<String, serde_json::Error> {
+        let indent = " ".repeat(indent);
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+        let mut buf = Vec::new();
+        let mut serializer = serde_json::Serializer::with_formatter(&mut buf, formatter);
+        self.serialize(&mut serializer)?;
+        // serde_json only ever writes valid UTF-8 
+        Ok(String::from_utf8(buf).expect("serde_json produced invalid UTF-8"))
+    }
+
+
+    /// A standalone HTML document that loads 3d-force-graph from a CDN and renders this graph,
+    /// i.e. one file you can open in a browser or attach to a message 
+    pub fn to_html(&self, title: &str) -> Result<String, serde_json::Error> {
+        // "</script>" inside a string would otherwise end the script element early 
+        let data = serde_json::to_string(self)?.replace("</", "<\\/");
+        Ok(format!(r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta charset="utf-8">
+  <title>{title}</title>
+  <style>body {{ margin: 0; }}</style>
+  <script src="https://unpkg.com/3d-force-graph"></script>
+</head>
+<body>
+  <div id="graph"></div>
+  <script>
+    const graph = {data};
+    // nodes and edges are collected by variant then id: 3d-force-graph wants flat arrays 
+    const flatten = collections => Object.values(collections).flatMap(collection => Object.values(collection));
+    ForceGraph3D()(document.getElementById('graph'))
+      .graphData({{nodes: flatten(graph.nodes), links: flatten(graph.edges)}});
+  </script>
+</body>
+</html>
+"#, title = escape_html(title), data = data))
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::test_support::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// an in-memory writer that stays readable after the graph is done with it 
+    #[derive(Clone, Default)]
+    struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn lines_of(buffers: &HashMap<String, SharedBuf>, variant: &str) -> Vec<serde_json::Value> {
+        let text = String::from_utf8(buffers[variant].0.borrow().clone()).unwrap();
+        text.lines().map(|l| serde_json::from_str(l).unwrap()).collect()
+    }
+
+    #[test]
+    fn ndjson_is_split_by_variant() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.add_node_from(&thing_of("Other", 7)).unwrap();
+        let mut buffers: HashMap<String, SharedBuf> = HashMap::new();
+        graph.write_nodes_by_variant(|variant| buffers.entry(variant.to_string()).or_default().clone()).unwrap();
+        let things = lines_of(&buffers, "Thing");
+        assert_eq!(things.len(), 2);
+        assert_eq!(things[0]["id"], id(1));
+        assert_eq!(things[1]["id"], id(2));
+        assert_eq!(lines_of(&buffers, "Other").len(), 1);
+
+        let mut edge_buffers: HashMap<String, SharedBuf> = HashMap::new();
+        graph.write_edges_by_variant(|variant| edge_buffers.entry(variant.to_string()).or_default().clone()).unwrap();
+        assert_eq!(lines_of(&edge_buffers, "link")[0]["id"], edge_id(1, 2));
+    }
+
+    #[test]
+    fn pretty_json_is_indented() {
+        let graph = graph_from_edges(&[(1, 2)]);
+        let pretty = graph.to_pretty_json_string().unwrap();
+        assert!(pretty.contains("\n  \"nodes\": {"));
+        let four = graph.to_pretty_json_string_with_indent(4).unwrap();
+        assert!(four.contains("\n    \"nodes\": {"));
+        let reparsed: serde_json::Value = serde_json::from_str(&four).unwrap();
+        assert_eq!(reparsed, serde_json::to_value(&graph).unwrap());
+    }
+
+    #[test]
+    fn html_embeds_the_graph() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.nodes.get_mut("Thing").unwrap().get_mut(&id(1)).unwrap()["name"] = "</script>".into();
+        let html = graph.to_html("Things & <stuff>").unwrap();
+        assert!(html.contains("<title>Things &amp; &lt;stuff&gt;</title>"));
+        assert!(html.contains("3d-force-graph"));
+        assert!(html.contains(&id(1)) && html.contains(&id(2)));
+        assert!(html.contains(&edge_id(1, 2)));
+        assert_eq!(html.matches("</script>").count(), 2);
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1)]);
+        graph.add_node_from(&thing_of("Other", 1)).unwrap();
+        graph.meta.zoom_id = Some(id(2));
+        let hyperedge = crate::fd3d::HyperEdge{variant: "coauthored", variant_pk: 1, id: "coauthored|1".to_string(), members: vec![id(3), id(1)], props: ()};
+        graph.add_hyperedge(&hyperedge).unwrap();
+        let (compact, ids) = graph.to_compact().unwrap();
+        assert_eq!(ids.len(), 4);
+        let edge = &compact.edges["link"][0];
+        assert_eq!(ids[edge["source"].as_u64().unwrap() as usize], id(1));
+        assert_eq!(compact.hyperedges["coauthored"][0]["members"], serde_json::json!([3, 1]));
+        let back = Graph::from_compact(compact.clone(), &ids).unwrap();
+        assert_eq!(back.nodes, graph.nodes);
+        assert_eq!(back.edges, graph.edges);
+        assert_eq!(back.hyperedges, graph.hyperedges);
+        assert_eq!(back.meta, graph.meta);
+        assert!(serde_json::to_string(&compact).unwrap().len() < serde_json::to_string(&graph).unwrap().len());
+        assert!(matches!(Graph::from_compact(compact.clone(), &ids[..1]), Err(GraphError::UnknownIndex(_))));
+        let mut invalid = compact;
+        invalid.edges.get_mut("link").unwrap()[0]["source"] = "Thing|1".into();
+        assert!(matches!(Graph::from_compact(invalid, &ids), Err(GraphError::InvalidCompactIndex(value)) if value == "Thing|1"));
+    }
+
+    #[test]
+    fn compact_leaves_missing_ends_and_rejects_what_it_cannot_key() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.edges.get_mut("link").unwrap().insert("loose".to_string(), serde_json::json!({"id": "loose", "source": null}));
+        let (compact, ids) = graph.to_compact().unwrap();
+        assert_eq!(ids, [id(1), id(2)]);
+        let back = Graph::from_compact(compact.clone(), &ids).unwrap();
+        assert_eq!(back.edges, graph.edges);
+        let mut unkeyed = compact;
+        unkeyed.edges.get_mut("link").unwrap().iter_mut().for_each(|edge| { edge.as_object_mut().unwrap().remove("id"); });
+        assert!(matches!(Graph::from_compact(unkeyed, &ids), Err(GraphError::MissingCompactId(_))));
+
+        graph.nodes.get_mut("Thing").unwrap().insert("odd".to_string(), 5.into());
+        assert!(matches!(graph.to_compact(), Err(GraphError::NotAnObject(id)) if id == "odd"));
+    }
+
+    #[test]
+    fn schema_reports_the_union_of_prop_keys() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.source_edge_target(&thing(2), &thing(3), "rated", crate::fd3d::Props::new().set("stars", 5)).unwrap();
+        let things = graph.nodes.get_mut("Thing").unwrap();
+        things.get_mut(&id(1)).unwrap()["props"] = serde_json::json!({"a": 1, "b": 2});
+        things.get_mut(&id(2)).unwrap()["props"] = serde_json::json!({"b": 3, "c": 4});
+        let schema = graph.schema();
+        let keys: Vec<&String> = schema.nodes["Thing"].iter().collect();
+        assert_eq!(keys, ["a", "b", "c"]);
+        assert!(schema.edges["link"].is_empty());
+        assert!(schema.edges["rated"].contains("stars"));
+    }
+
+    #[test]
+    fn cytoscape_elements_have_ids() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.source_edge_target(&thing(2), &thing(3), "rated", crate::fd3d::Props::new().set("stars", 5)).unwrap();
+        let cy = graph.to_cytoscape();
+        let nodes = cy["elements"]["nodes"].as_array().unwrap();
+        let ids: Vec<&str> = nodes.iter().map(|n| n["data"]["id"].as_str().unwrap()).collect();
+        assert_eq!(ids, [id(1), id(2), id(3)]);
+        assert_eq!(nodes[0]["data"]["name"], "Thing 1");
+        let edges = cy["elements"]["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0]["data"]["id"], edge_id(1, 2));
+        assert_eq!(edges[0]["data"]["source"], id(1));
+        assert_eq!(edges[1]["data"]["stars"], 5);
+        assert_eq!(edges[1]["data"]["target"], id(3));
+    }
+
+    #[test]
+    fn json_graph_format_has_required_fields() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.source_edge_target(&thing(2), &thing(3), "rated", crate::fd3d::Props::new().set("stars", 5)).unwrap();
+        let jgf = graph.to_json_graph_format();
+        let nodes = jgf["graph"]["nodes"].as_object().unwrap();
+        assert_eq!(nodes.len(), 3);
+        for node in nodes.values() {
+            assert!(node["label"].is_string());
+            assert!(node["metadata"].is_object());
+        }
+        assert_eq!(nodes[&id(1)]["metadata"]["variant"], "Thing");
+        let edges = jgf["graph"]["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 2);
+        for edge in edges {
+            assert!(nodes.contains_key(edge["source"].as_str().unwrap()));
+            assert!(nodes.contains_key(edge["target"].as_str().unwrap()));
+        }
+        assert_eq!(edges[1]["relation"], "rated");
+        assert_eq!(edges[1]["metadata"]["props"]["stars"], 5);
+    }
+
+    #[test]
+    fn minimal_json_has_no_props() {
+        let mut graph = graph_from_edges(&[(1, 2)]);
+        graph.source_edge_target(&thing(2), &thing(3), "rated", crate::fd3d::Props::new().set("stars", 5)).unwrap();
+        let minimal = graph.to_json_minimal();
+        assert!(!minimal.to_string().contains("props"));
+        assert_eq!(minimal["nodes"]["Thing"][&id(1)], serde_json::json!({"id": id(1), "name": "Thing 1", "variant": "Thing"}));
+        assert_eq!(minimal["edges"]["rated"]["2|rated|3"]["target"], id(3));
+        assert!(minimal.to_string().len() < serde_json::to_string(&graph).unwrap().len());
+    }
+
+    #[test]
+    fn force_graph_json_has_flat_nodes_and_links() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.nodes.get_mut("Thing").unwrap().remove(&id(3));
+        let flat = graph.to_force_graph_json(true);
+        let nodes = flat["nodes"].as_array().unwrap();
+        let links = flat["links"].as_array().unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0]["id"], id(1));
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0]["id"], edge_id(1, 2));
+        assert_eq!(links[0]["source"], id(1));
+        assert_eq!(links[0]["target"], id(2));
+        assert_eq!(graph.to_force_graph_json(false)["nodes"]["Thing"][&id(1)]["name"], "Thing 1");
+    }
+
+    /// a minimal RFC 6902 applier for the operations json_patch_to emits 
+    fn apply_patch(document: &mut serde_json::Value, patch: &serde_json::Value) {
+        for op in patch.as_array().unwrap() {
+            let path = op["path"].as_str().unwrap();
+            if path.is_empty() {
+                *document = op["value"].clone();
+                continue;
+            }
+            let (parent, key) = path.rsplit_once('/').unwrap();
+            let key = key.replace("~1", "/").replace("~0", "~");
+            let parent = document.pointer_mut(parent).unwrap().as_object_mut().unwrap();
+            match op["op"].as_str().unwrap() {
+                "remove" => {
+                    parent.remove(&key).unwrap();
+                },
+                "add" | "replace" => {
+                    parent.insert(key, op["value"].clone());
+                },
+                other => panic!("unexpected op {}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn json_patch_turns_one_graph_into_the_other() {
+        let before = graph_from_edges(&[(1, 2), (2, 3)]);
+        let mut after = graph_from_edges(&[(1, 2), (3, 4)]);
+        after.source_edge_target(&thing_of("a/b~c", 1), &thing(1), "cites", ()).unwrap();
+        after.nodes.get_mut("Thing").unwrap().get_mut(&id(1)).unwrap()["name"] = "renamed".into();
+        after.set_layout_seed(3);
+        let patch = before.json_patch_to(&after);
+        assert!(patch.as_array().unwrap().iter().any(|op| op["op"] == "remove"));
+        let mut document = serde_json::to_value(&before).unwrap();
+        apply_patch(&mut document, &patch);
+        assert_eq!(document, serde_json::to_value(&after).unwrap());
+        assert!(after.json_patch_to(&after).as_array().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_round_trips() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3)]);
+        graph.source_edge_target(&thing(3), &thing_of("Other", 1), "cites", crate::fd3d::Props::new().set("weight", 2.5).set("note", "x")).unwrap();
+        graph.set_layout_seed(11);
+        graph.set_variant_style("Thing", serde_json::json!({"color": "red", "size": -4}));
+        let bytes = graph.to_msgpack().unwrap();
+        let decoded = Graph::from_msgpack(&bytes).unwrap();
+        assert_eq!(serde_json::to_value(&decoded).unwrap(), serde_json::to_value(&graph).unwrap());
+        // arbitrary_precision numbers go through serde as a wrapper map holding their digits, which msgpack spells out 
+        #[cfg(not(feature = "arbitrary_precision"))]
+        assert!(bytes.len() < serde_json::to_vec(&graph).unwrap().len());
+    }
+
+    #[test]
+    fn estimated_json_bytes_is_close_to_the_real_length() {
+        let mut graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4)]);
+        graph.source_edge_target(&thing(1), &thing(4), "cites", crate::fd3d::Props::new().set("weight", 0.25).set("ok", true)).unwrap();
+        graph.set_variant_style("Thing", serde_json::json!({"color": "#fff"}));
+        let exact = graph.json_byte_len();
+        assert_eq!(exact, serde_json::to_string(&graph).unwrap().len());
+        let estimate = graph.estimated_json_bytes();
+        assert!(estimate >= exact * 9 / 10 && estimate <= exact * 11 / 10, "{} vs {}", estimate, exact);
+    }
+}
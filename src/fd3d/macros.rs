@@ -0,0 +1,74 @@
+//! The graph! macro for writing small graphs tersely, e.g. as test fixtures 
+
+/// Build a Graph from a terse list of nodes and edges, each item ending in a semicolon, e.g.
+/// `graph! { node "A" "Alpha"; node "B" "Beta"; edge "A" -> "B" : "knows"; }`
+///
+/// * `node ID NAME;` adds a node of variant "Node" with that id and name
+/// * `node ID NAME : VARIANT;` adds it under VARIANT instead
+/// * `edge SOURCE -> TARGET : VARIANT;` adds an edge of VARIANT between two ids, with the id `"SOURCE"|VARIANT|"TARGET"`
+///   (the ids Debug-quoted, as source_edge_target quotes pks), so it matches the same link built through source_edge_target
+///
+/// Each of the pieces is a string literal or any other single token tree, such as a variable or a (parenthesized expression).
+/// Props are (), the node's variant_pk is its id and edges are not checked for missing nodes: call validate for that.
+#[macro_export]
+macro_rules! graph {
+    (@items $graph:ident;) => {};
+    (@items $graph:ident; node $id:tt $name:tt; $($rest:tt)*) => {
+        $crate::graph!(@items $graph; node $id $name : "Node"; $($rest)*)
+    };
+    (@items $graph:ident; node $id:tt $name:tt : $variant:tt; $($rest:tt)*) => {
+        let id: &str = $id;
+        let node = $crate::fd3d::Node::new($variant, id, id.to_string(), ::std::string::ToString::to_string(&$name), ());
+        $graph.add_node(&node).expect("graph! nodes always serialize");
+        $crate::graph!(@items $graph; $($rest)*)
+    };
+    (@items $graph:ident; edge $source:tt -> $target:tt : $variant:tt; $($rest:tt)*) => {
+        let (source, target, variant): (&str, &str, &str) = ($source, $target, $variant);
+        let edge = $crate::fd3d::Edge{
+            variant,
+            variant_pk: (source, target),
+            id: format!("{:?}|{}|{:?}", source, variant, target),
+            source: source.to_string(),
+            target: target.to_string(),
+            props: (),
+            valid_from: None,
+            valid_to: None,
+            label: Some(variant.to_string()),
+            bundle: None,
+            directed: None,
+        };
+        let json = edge.to_edge_json().expect("graph! edges always serialize");
+        $graph.edges.entry(variant.to_string()).or_default().insert(edge.id, json);
+        $crate::graph!(@items $graph; $($rest)*)
+    };
+    ($($items:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut graph = $crate::fd3d::Graph::new();
+        $crate::graph!(@items graph; $($items)*);
+        graph
+    }};
+}
+
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn graph_macro_builds_nodes_and_edges() {
+        let beta = "Beta";
+        let graph = crate::graph! {
+            node "A" "Alpha";
+            node "B" beta;
+            node "C" "Gamma" : "Person";
+            edge "A" -> "B" : "knows";
+            edge "B" -> "C" : "knows";
+            edge "C" -> "A" : "likes";
+        };
+        assert_eq!(graph.nodes["Node"].len(), 2);
+        assert_eq!(graph.nodes["Person"].len(), 1);
+        assert_eq!(graph.nodes["Node"]["B"]["name"], "Beta");
+        assert_eq!(graph.edges["knows"].len(), 2);
+        assert_eq!(graph.edges["likes"]["\"C\"|likes|\"A\""]["target"], "A");
+        assert!(graph.validate().is_ok());
+        assert!(crate::graph! {}.nodes.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+//! A small 2D force-directed layout computed in Rust and rendered as SVG, for thumbnails without a browser 
+
+use std::{collections::HashMap, fmt::Write};
+use super::{Graph, edge_ends, export::escape_html};
+
+
+/// splitmix64: a tiny deterministic generator, good enough to scatter the initial positions 
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+
+/// Fruchterman-Reingold: nodes repel each other with force k²/d, edges pull their ends together with force d²/k,
+/// and each step moves a node at most a temperature that cools linearly to zero 
+fn fruchterman_reingold(n: usize, edges: &[(usize, usize)], width: f64, height: f64, iterations: usize, seed: u64) -> Vec<(f64, f64)> {
+    let mut rng = SplitMix64(seed);
+    let mut positions: Vec<(f64, f64)> = (0..n).map(|_| (rng.next_f64() * width, rng.next_f64() * height)).collect();
+    if n == 0 {
+        return positions;
+    }
+    let k = (width * height / n as f64).sqrt();
+    let start_temperature = width.min(height) / 10.0;
+    for iteration in 0..iterations {
+        let mut displacement = vec![(0.0, 0.0); n];
+        for u in 0..n {
+            for v in (u + 1)..n {
+                let (dx, dy) = (positions[u].0 - positions[v].0, positions[u].1 - positions[v].1);
+                let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+                let force = k * k / distance;
+                displacement[u].0 += dx / distance * force;
+                displacement[u].1 += dy / distance * force;
+                displacement[v].0 -= dx / distance * force;
+                displacement[v].1 -= dy / distance * force;
+            }
+        }
+        for (s, t) in edges {
+            let (dx, dy) = (positions[*s].0 - positions[*t].0, positions[*s].1 - positions[*t].1);
+            let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+            let force = distance * distance / k;
+            displacement[*s].0 -= dx / distance * force;
+            displacement[*s].1 -= dy / distance * force;
+            displacement[*t].0 += dx / distance * force;
+            displacement[*t].1 += dy / distance * force;
+        }
+        let temperature = start_temperature * (1.0 - iteration as f64 / iterations as f64);
+        for (position, (dx, dy)) in positions.iter_mut().zip(displacement) {
+            let length = (dx * dx + dy * dy).sqrt().max(0.01);
+            let step = length.min(temperature);
+            position.0 = (position.0 + dx / length * step).clamp(0.0, width);
+            position.1 = (position.1 + dy / length * step).clamp(0.0, height);
+        }
+    }
+    positions
+}
+
+
+impl Graph {
+    /// Lay the graph out in 2D with iterations rounds of Fruchterman-Reingold and render it as an SVG document of
+    /// width x height pixels: one line per edge, then one circle per node titled with its name.
+    /// The initial positions are drawn from seed, so the same graph and seed always give the same picture.
+    /// Each round costs O(V² + E), which is fine for thumbnails of up to a few thousand nodes. Dangling edges are not drawn 
+    pub fn to_svg(&self, width: u32, height: u32, iterations: usize, seed: u64) -> String {
+        const RADIUS: f64 = 4.0;
+        let mut nodes: Vec<(&str, &serde_json::Value)> = self.nodes.values()
+            .flat_map(|collection| collection.iter().map(|(id, node)| (id.as_str(), node)))
+            .collect();
+        nodes.sort_by_key(|(id, _node)| *id);
+        let index: HashMap<&str, usize> = nodes.iter().enumerate().map(|(i, (id, _node))| (*id, i)).collect();
+        let mut edges: Vec<(&str, &str, (usize, usize))> = self.edges.iter()
+            .flat_map(|(variant, collection)| collection.iter().map(move |(id, edge)| (variant.as_str(), id.as_str(), edge)))
+            .filter_map(|(variant, id, edge)| {
+                let (source, target) = edge_ends(edge);
+                Some((variant, id, (*index.get(source)?, *index.get(target)?)))
+            })
+            .collect();
+        edges.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+        let edges: Vec<(usize, usize)> = edges.into_iter().map(|(_variant, _id, ends)| ends).collect();
+        // keep the circles inside the canvas 
+        let (inner_width, inner_height) = ((width as f64 - 2.0 * RADIUS).max(0.0), (height as f64 - 2.0 * RADIUS).max(0.0));
+        let positions = fruchterman_reingold(nodes.len(), &edges, inner_width, inner_height, iterations, seed);
+        let position = |i: usize| (positions[i].0 + RADIUS, positions[i].1 + RADIUS);
+
+        let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", width, height);
+        // writing to a String cannot fail 
+        for (s, t) in &edges {
+            let ((x1, y1), (x2, y2)) = (position(*s), position(*t));
+            let _ = writeln!(svg, "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#999\" stroke-width=\"1\"/>", x1, y1, x2, y2);
+        }
+        for (i, (id, node)) in nodes.iter().enumerate() {
+            let (x, y) = position(i);
+            let name = node["name"].as_str().unwrap_or(id);
+            let _ = writeln!(svg, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{}\" fill=\"#1f77b4\"><title>{}</title></circle>", x, y, RADIUS, escape_html(name));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+
+    #[test]
+    fn svg_has_a_circle_per_node_and_is_reproducible() {
+        let graph = graph_from_edges(&[(1, 2), (2, 3), (3, 1), (3, 4)]);
+        let svg = graph.to_svg(200, 100, 50, 7);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<circle").count(), 4);
+        assert_eq!(svg.matches("<line").count(), 4);
+        assert!(svg.contains("<title>Thing 1</title>"));
+        assert_eq!(svg, graph.to_svg(200, 100, 50, 7));
+        assert_ne!(svg, graph.to_svg(200, 100, 50, 8));
+    }
+}
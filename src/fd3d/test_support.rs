@@ -0,0 +1,62 @@
+//! Fixtures shared by the unit tests of the fd3d module 
+
+use super::{Graph, ToNode, ToNodeJSON};
+
+
+/// A minimal ToNode implementor: the variant is a plain String and the primary key an i32,
+/// so node ids look like "Thing|1" 
+pub(crate) struct Thing {
+    pub variant: &'static str,
+    pub pk: i32,
+    pub name: String,
+}
+
+impl ToNode<String, i32, ()> for Thing {
+    fn node_variant(&self) -> String {
+        self.variant.to_string()
+    }
+    fn node_pk(&self) -> i32 {
+        self.pk
+    }
+    fn node_name(&self) -> String {
+        self.name.clone()
+    }
+    fn node_props(&self) {}
+}
+
+impl ToNodeJSON<String, i32, ()> for Thing {}
+
+
+/// A "Thing" node with primary key pk 
+pub(crate) fn thing(pk: i32) -> Thing {
+    thing_of("Thing", pk)
+}
+
+/// A node of the given variant with primary key pk 
+pub(crate) fn thing_of(variant: &'static str, pk: i32) -> Thing {
+    Thing{variant, pk, name: format!("{} {}", variant, pk)}
+}
+
+/// The node id of thing(pk) 
+pub(crate) fn id(pk: i32) -> String {
+    format!("Thing|{}", pk)
+}
+
+/// The edge id of a "link" edge from thing(a) to thing(b) 
+pub(crate) fn edge_id(a: i32, b: i32) -> String {
+    format!("{}|link|{}", a, b)
+}
+
+/// Add a "link" edge (and both endpoint nodes) from thing(a) to thing(b) 
+pub(crate) fn link(graph: &mut Graph, a: i32, b: i32) {
+    graph.source_edge_target(&thing(a), &thing(b), "link", ()).unwrap();
+}
+
+/// Build a graph of Things from a list of directed (source, target) links 
+pub(crate) fn graph_from_edges(edges: &[(i32, i32)]) -> Graph {
+    let mut graph = Graph::new();
+    for (a, b) in edges {
+        link(&mut graph, *a, *b);
+    }
+    graph
+}